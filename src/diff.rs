@@ -0,0 +1,282 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::json_models::gltf::Gltf;
+use crate::managers::{Manager, PacketTarget};
+use crate::selectors::{object_name, Category};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// How the objects holding packet references are matched between two versions of a document.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ObjectMatch {
+    /// By category and index, e.g. `nodes[3]`.
+    Index,
+    /// By category and name, e.g. `nodes/name=Wheel_FL`. Objects without a unique name are
+    /// matched by index.
+    Name,
+}
+
+/// The metadata of a glTF document that is compared: its packets, the packet each object
+/// references and the extensions it uses.
+pub struct Metadata {
+    pub packets: Vec<Value>,
+    /// Each object holding a packet reference, labelled as `ObjectMatch` requires, with its packet
+    /// index.
+    pub references: Vec<(String, u64)>,
+    pub extensions_used: Vec<String>,
+}
+
+impl Metadata {
+    /// Reads the metadata of a document through its manager. A document without the extension has
+    /// no packets.
+    pub fn read(manager: &dyn Manager, object_match: ObjectMatch) -> Metadata {
+        Metadata {
+            packets: manager.get_packets().unwrap_or_default(),
+            references: manager
+                .get_packet_references()
+                .into_iter()
+                .map(|(target, packet)| (label(manager.get_gltf(), &target, object_match), packet))
+                .collect(),
+            extensions_used: manager.get_gltf().extensions_used.clone().unwrap_or_default(),
+        }
+    }
+}
+
+fn label(gltf: &Gltf, target: &PacketTarget, object_match: ObjectMatch) -> String {
+    if object_match == ObjectMatch::Name {
+        if let Some((category, index)) = Category::of(target) {
+            let objects = category.objects(gltf);
+            if let Some(name) = objects.get(index).and_then(object_name) {
+                if objects.iter().filter(|o| object_name(o) == Some(name)).count() == 1 {
+                    return format!("{}/name={}", category.name(), name);
+                }
+            }
+        }
+    }
+
+    target.to_string()
+}
+
+/// A top-level property of a packet that was added, removed or changed. A packet that was added
+/// or removed as a whole is reported property by property.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct PropertyChange {
+    pub packet: usize,
+    pub property: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// An object whose packet reference was added, removed or changed.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ReferenceChange {
+    pub target: String,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+}
+
+/// An entry of `extensionsUsed` that was added or removed.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ExtensionChange {
+    pub extension: String,
+    pub added: bool,
+}
+
+#[derive(Serialize, Default, PartialEq, Debug)]
+pub struct MetadataDiff {
+    pub properties: Vec<PropertyChange>,
+    pub references: Vec<ReferenceChange>,
+    #[serde(rename = "extensionsUsed")]
+    pub extensions_used: Vec<ExtensionChange>,
+}
+
+impl MetadataDiff {
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty() && self.references.is_empty() && self.extensions_used.is_empty()
+    }
+}
+
+/// The properties of a packet. A packet that is not an object is compared as a whole, under an
+/// empty property name.
+fn properties(packet: Option<&Value>) -> Map<String, Value> {
+    match packet {
+        Some(Value::Object(object)) => object.clone(),
+        Some(value) => {
+            let mut map = Map::new();
+            map.insert(String::new(), value.clone());
+            map
+        }
+        None => Map::new(),
+    }
+}
+
+/// Compares two versions of metadata. Packets are matched by index and compared property by
+/// property; key order does not matter. Packet references are matched by object label.
+pub fn diff_metadata(before: &Metadata, after: &Metadata) -> MetadataDiff {
+    let mut diff = MetadataDiff::default();
+
+    for packet in 0..before.packets.len().max(after.packets.len()) {
+        let old = properties(before.packets.get(packet));
+        let new = properties(after.packets.get(packet));
+
+        let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            let (old_value, new_value) = (old.get(key), new.get(key));
+            if old_value != new_value {
+                diff.properties.push(PropertyChange {
+                    packet,
+                    property: key.clone(),
+                    before: old_value.cloned(),
+                    after: new_value.cloned(),
+                });
+            }
+        }
+    }
+
+    let reference = |references: &[(String, u64)], target: &str| {
+        references.iter().find(|(t, _)| t == target).map(|(_, p)| *p)
+    };
+    let mut targets: Vec<&String> = vec![];
+    for (target, _) in before.references.iter().chain(after.references.iter()) {
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    for target in targets {
+        let (old, new) = (reference(&before.references, target), reference(&after.references, target));
+        if old != new {
+            diff.references.push(ReferenceChange {
+                target: target.clone(),
+                before: old,
+                after: new,
+            });
+        }
+    }
+
+    for extension in &before.extensions_used {
+        if !after.extensions_used.contains(extension) {
+            diff.extensions_used.push(ExtensionChange {
+                extension: extension.clone(),
+                added: false,
+            });
+        }
+    }
+    for extension in &after.extensions_used {
+        if !before.extensions_used.contains(extension) {
+            diff.extensions_used.push(ExtensionChange {
+                extension: extension.clone(),
+                added: true,
+            });
+        }
+    }
+
+    diff
+}
+
+impl fmt::Display for PropertyChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.property.is_empty() {
+            write!(f, "packets[{}]: ", self.packet)?;
+        } else {
+            write!(f, "packets[{}] {}: ", self.packet, self.property)?;
+        }
+
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => write!(f, "{} -> {}", before, after),
+            (None, Some(after)) => write!(f, "added {}", after),
+            (Some(before), None) => write!(f, "removed {}", before),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ReferenceChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.before, self.after) {
+            (Some(before), Some(after)) => write!(f, "{}: packet {} -> {}", self.target, before, after),
+            (None, Some(after)) => write!(f, "{}: packet {} added", self.target, after),
+            (Some(before), None) => write!(f, "{}: packet {} removed", self.target, before),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ExtensionChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = if self.added { "added" } else { "removed" };
+        write!(f, "extensionsUsed: {} {}", change, self.extension)
+    }
+}
+
+impl fmt::Display for MetadataDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.properties {
+            writeln!(f, "{}", change)?;
+        }
+        for change in &self.references {
+            writeln!(f, "{}", change)?;
+        }
+        for change in &self.extensions_used {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diffs_metadata() {
+        let before = Metadata {
+            packets: vec![json!({ "dc:title": "Old", "dc:source": "Source", "@context": { "dc": "http://purl.org/dc/elements/1.1/" } })],
+            references: vec![("asset".to_string(), 0), ("nodes[3]".to_string(), 0)],
+            extensions_used: vec!["KHR_xmp".to_string()],
+        };
+        let after = Metadata {
+            packets: vec![
+                json!({ "@context": { "dc": "http://purl.org/dc/elements/1.1/" }, "dc:title": "New" }),
+                json!({ "dc:rights": "CC BY 4.0" }),
+            ],
+            references: vec![("asset".to_string(), 0), ("nodes[3]".to_string(), 1), ("nodes[4]".to_string(), 1)],
+            extensions_used: vec!["KHR_xmp_json_ld".to_string()],
+        };
+
+        let diff = diff_metadata(&before, &after);
+        assert_eq!(
+            diff.to_string(),
+            "packets[0] dc:source: removed \"Source\"\n\
+             packets[0] dc:title: \"Old\" -> \"New\"\n\
+             packets[1] dc:rights: added \"CC BY 4.0\"\n\
+             nodes[3]: packet 0 -> 1\n\
+             nodes[4]: packet 1 added\n\
+             extensionsUsed: removed KHR_xmp\n\
+             extensionsUsed: added KHR_xmp_json_ld\n"
+        );
+
+        assert!(diff_metadata(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn labels_objects_by_name() {
+        let gltf: Gltf = serde_json::from_value(json!({
+            "asset": { "version": "2.0" },
+            "nodes": [{ "name": "Body" }, { "name": "Wheel" }, { "name": "Wheel" }, {}]
+        }))
+        .unwrap();
+
+        let labels: Vec<String> = (0..4)
+            .map(|i| label(&gltf, &PacketTarget::Nodes(i), ObjectMatch::Name))
+            .collect();
+        assert_eq!(labels, vec!["nodes/name=Body", "nodes[1]", "nodes[2]", "nodes[3]"]);
+        assert_eq!(label(&gltf, &PacketTarget::Nodes(0), ObjectMatch::Index), "nodes[0]");
+        assert_eq!(label(&gltf, &PacketTarget::Asset, ObjectMatch::Name), "asset");
+    }
+}
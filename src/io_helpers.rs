@@ -4,16 +4,53 @@
 
 use crate::json_models::gltf::Gltf;
 use crate::json_models::khr_xmp::KhrXmp;
+use gltf::Glb;
+use std::borrow::Cow;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+#[cfg(unix)]
+use std::fs::TryLockError;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
 use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use crate::json_models::khr_xmp_json_ld::KhrXmpJsonLd;
+use crate::xmp::reader::parse_xmp;
+use crate::xliff::{parse_xliff, Translation};
+
+/// The path that reads from stdin or writes to stdout.
+pub const STDIO_PATH: &str = "-";
+
+/// Whether a path stands for stdin or stdout.
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Reads stdin to its end on first use. The contents are kept, since the input is opened again
+/// once its format has been detected.
+fn stdin_contents() -> Result<&'static [u8], Box<dyn Error>> {
+    static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
+
+    if let Some(contents) = STDIN.get() {
+        return Ok(contents);
+    }
+    let mut contents = vec![];
+    std::io::stdin().read_to_end(&mut contents)?;
+    Ok(STDIN.get_or_init(|| contents))
+}
+
+/// Opens a file for reading, or stdin for the `-` path.
+pub fn open_reader(path: &Path) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    if is_stdio(path) {
+        return Ok(Box::new(stdin_contents()?));
+    }
 
-pub fn open_reader(path: &Path) -> Result<BufReader<File>, Box<dyn Error>> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
-    Ok(reader)
+    Ok(Box::new(reader))
 }
 
 pub fn read_legacy_json<T: Read>(reader: T) -> Result<KhrXmp, Box<dyn Error>> {
@@ -26,19 +63,228 @@ pub fn read_json<T: Read>(reader: T) -> Result<KhrXmpJsonLd, Box<dyn Error>> {
     Ok(json)
 }
 
+/// Reads an XMP (RDF/XML) file and converts it into `KHR_xmp_json_ld` packets.
+pub fn read_xmp<T: Read>(mut reader: T) -> Result<KhrXmpJsonLd, Box<dyn Error>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    Ok(parse_xmp(&text)?)
+}
+
+pub fn read_xliff<T: Read>(mut reader: T) -> Result<Vec<Translation>, Box<dyn Error>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    Ok(parse_xliff(&text)?)
+}
+
 pub fn read_gltf<T: Read>(reader: T) -> Result<Gltf, Box<dyn Error>> {
     let gltf = serde_json::from_reader(reader)?;
     Ok(gltf)
 }
 
-pub fn open_writer(path: &Path) -> Result<BufWriter<File>, Box<dyn Error>> {
+/// The parsed JSON chunk and the raw BIN chunk (if any) of a binary glTF file.
+pub type GlbContents = (Gltf, Option<Vec<u8>>);
+
+pub fn read_glb<T: Read>(reader: T) -> Result<GlbContents, Box<dyn Error>> {
+    let glb = Glb::from_reader(reader)?;
+    let gltf = serde_json::from_slice(glb.json.as_ref())?;
+    Ok((gltf, glb.bin.map(|b| b.into_owned())))
+}
+
+/// The number of leading bytes read to detect the format of an input file.
+const SNIFF_LENGTH: u64 = 512;
+
+/// The container format of a glTF asset.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputType {
+    Gltf,
+    Glb,
+}
+
+impl InputType {
+    /// Parses a format name as given to `--input-format`.
+    pub fn from_name(name: &str) -> Option<InputType> {
+        match name.to_ascii_lowercase().as_str() {
+            "gltf" => Some(InputType::Gltf),
+            "glb" => Some(InputType::Glb),
+            _ => None,
+        }
+    }
+
+    /// The format suggested by the extension of a path, if any.
+    pub fn from_extension(path: &Path) -> Option<InputType> {
+        path.extension().and_then(|e| e.to_str()).and_then(InputType::from_name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InputType::Gltf => "glTF",
+            InputType::Glb => "GLB",
+        }
+    }
+}
+
+/// Detects the format of a glTF asset from its first bytes: the `glTF` magic of a GLB header, or
+/// the `{` opening a JSON document. Returns `None` if the content looks like neither.
+pub fn sniff_format<T: Read>(reader: T) -> Result<Option<InputType>, Box<dyn Error>> {
+    let mut bytes = vec![];
+    reader.take(SNIFF_LENGTH).read_to_end(&mut bytes)?;
+
+    if let Some(header) = bytes.strip_prefix(b"glTF") {
+        return match header.get(..4) {
+            Some(&[a, b, c, d]) if u32::from_le_bytes([a, b, c, d]) == 2 => Ok(Some(InputType::Glb)),
+            Some(&[a, b, c, d]) => Err(format!(
+                "The input is a GLB version {} file. Only version 2 is supported.",
+                u32::from_le_bytes([a, b, c, d])
+            )
+            .into()),
+            _ => Err("The input has a truncated GLB header.".into()),
+        };
+    }
+
+    let json = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&bytes);
+    match json.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') => Ok(Some(InputType::Gltf)),
+        _ => Ok(None),
+    }
+}
+
+/// Opens a file for writing, or stdout for the `-` path.
+pub fn open_writer(path: &Path) -> Result<Box<dyn Write>, Box<dyn Error>> {
+    if is_stdio(path) {
+        return Ok(Box::new(BufWriter::new(std::io::stdout())));
+    }
+
     let file = File::create(path)?;
     let writer = BufWriter::new(file);
-    Ok(writer)
+    Ok(Box::new(writer))
+}
+
+/// Creates a new, empty file with an unpredictable name in the temporary directory. The file is
+/// created exclusively, so an existing file or symlink at that path is never written through, and
+/// on Unix only its owner can read it.
+pub fn create_temporary_file() -> Result<PathBuf, Box<dyn Error>> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    // Retry in the unlikely case that the name is taken.
+    let mut attempts = 0;
+    loop {
+        let random = RandomState::new().build_hasher().finish();
+        let path = std::env::temp_dir().join(format!("gltfxmp-{}-{:016x}.tmp", std::process::id(), random));
+        match options.open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 8 => attempts += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// The path of the temporary file an in-place edit of `path` is written to, next to the file so
+/// that it can be renamed over it.
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Replaces a file with the output of `write`, which is given a temporary file in the same
+/// directory. Only once `write` succeeds is the temporary file synced to disk and renamed over the
+/// original, so a failure never leaves a half-written file behind. If `backup_suffix` is given,
+/// the original is first copied to its path with that suffix appended.
+///
+/// On Unix, an advisory lock is held on the original meanwhile, so that concurrent edits of the
+/// same file fail instead of overwriting each other.
+pub fn replace_file<F>(path: &Path, backup_suffix: Option<&str>, write: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&Path) -> Result<(), Box<dyn Error>>,
+{
+    let original = File::open(path)?;
+    // Locks are mandatory on Windows and would stop the input from being read, so they are only
+    // taken on Unix, where they are advisory.
+    #[cfg(unix)]
+    original.try_lock().map_err(|e| match e {
+        TryLockError::WouldBlock => format!("{} is being edited by another process.", path.display()).into(),
+        TryLockError::Error(e) => Box::<dyn Error>::from(e),
+    })?;
+
+    let temporary = temporary_path(path);
+    OpenOptions::new().write(true).create_new(true).open(&temporary)?;
+
+    let result = write(&temporary).and_then(|_| {
+        let file = OpenOptions::new().write(true).open(&temporary)?;
+        file.set_permissions(original.metadata()?.permissions())?;
+        file.sync_all()?;
+
+        if let Some(suffix) = backup_suffix {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(suffix);
+            std::fs::copy(path, backup)?;
+        }
+        std::fs::rename(&temporary, path)?;
+        sync_parent(path)?;
+        Ok(())
+    });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Syncs the directory holding `path`, so that a rename within it is durable. Directories cannot
+/// be synced this way on Windows.
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
 }
 
-pub fn write_gltf<T: Write>(writer: T, gltf: &Gltf) -> Result<(), Box<dyn Error>> {
-    serde_json::to_writer_pretty(writer, gltf)?;
+pub fn write_legacy_json<T: Write>(mut writer: T, metadata: &KhrXmp) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(&mut writer, metadata)?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn write_json<T: Write>(mut writer: T, metadata: &KhrXmpJsonLd) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(&mut writer, metadata)?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn write_gltf<T: Write>(mut writer: T, gltf: &Gltf) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(&mut writer, gltf)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes a binary glTF file using the given JSON and BIN chunk. Chunk lengths and padding are
+/// computed by the gltf crate.
+pub fn write_glb<T: Write>(mut writer: T, gltf: &Gltf, bin: Option<Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    let json_data = serde_json::to_string_pretty(gltf)?;
+    let glb = Glb {
+        header: gltf::binary::Header {
+            magic: *b"glTF",
+            version: 2,
+            // Recalculated by `to_writer`.
+            length: 0,
+        },
+        json: Cow::Owned(json_data.into_bytes()),
+        bin: bin.map(Cow::Owned),
+    };
+    glb.to_writer(&mut writer)?;
+    writer.flush()?;
 
     Ok(())
 }
@@ -50,7 +296,61 @@ mod test {
 
     const EXAMPLE_SIMPLE_JSON_PATH: &str = "./examples/sample.khr_xmp.json";
     const BOX_PATH: &str = "./examples/Box.gltf";
-    const SCIFI_HELMET_PATH: &str = "./glTF-Sample-Models/2.0/SciFiHelmet/glTF/SciFiHelmet.gltf";
+    const BOX_GLB_PATH: &str = "./examples/Box.glb";
+    const EXAMPLE_XMP_PATH: &str = "./examples/sample.xmp";
+    const EXAMPLE_JSON_LD_PATH: &str = "./examples/sample.khr_xmp_json_ld.json";
+
+    #[test]
+    fn sniffs_formats() {
+        assert_eq!(sniff_format(open_reader(Path::new(BOX_PATH)).unwrap()).unwrap(), Some(InputType::Gltf));
+        assert_eq!(sniff_format(open_reader(Path::new(BOX_GLB_PATH)).unwrap()).unwrap(), Some(InputType::Glb));
+        assert_eq!(sniff_format(&b"\xef\xbb\xbf \n\t{}"[..]).unwrap(), Some(InputType::Gltf));
+        assert_eq!(sniff_format(&b"<?xml"[..]).unwrap(), None);
+        assert_eq!(sniff_format(&b""[..]).unwrap(), None);
+        assert!(sniff_format(&b"glTF\x01\x00\x00\x00"[..]).is_err());
+        assert!(sniff_format(&b"glTF\x02"[..]).is_err());
+
+        assert_eq!(InputType::from_extension(Path::new("model.GLB")), Some(InputType::Glb));
+        assert_eq!(InputType::from_extension(Path::new("0123abcd")), None);
+    }
+
+    #[test]
+    fn creates_unique_temporary_files() {
+        let first = create_temporary_file().unwrap();
+        let second = create_temporary_file().unwrap();
+        assert_ne!(first, second);
+        assert!(first.exists() && second.exists());
+
+        std::fs::remove_file(first).unwrap();
+        std::fs::remove_file(second).unwrap();
+    }
+
+    #[test]
+    fn replaces_files_atomically() {
+        let path = std::env::temp_dir().join(format!("gltfxmp-replace-{}.gltf", std::process::id()));
+        let backup = path.with_extension("gltf.bak");
+        std::fs::write(&path, "old").unwrap();
+
+        replace_file(&path, Some(".bak"), |temporary| {
+            assert_eq!(std::fs::read_to_string(&path)?, "old");
+            std::fs::write(temporary, "new")?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "old");
+
+        let result = replace_file(&path, None, |temporary| {
+            std::fs::write(temporary, "partial")?;
+            Err("failed".into())
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert!(!temporary_path(&path).exists());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&backup).unwrap();
+    }
 
     #[test]
     fn can_open_and_read_json() {
@@ -59,7 +359,7 @@ mod test {
         let reader = open_reader(path);
         assert!(reader.is_ok());
 
-        let json = read_khr_xmp_json(reader.unwrap());
+        let json = read_legacy_json(reader.unwrap());
         assert!(json.is_ok());
 
         let expected = read_to_string(path).unwrap();
@@ -73,12 +373,42 @@ mod test {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn written_json_matches_input() {
+        let path = Path::new(EXAMPLE_JSON_LD_PATH);
+        let json = read_json(open_reader(path).unwrap()).unwrap();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write_json(&mut buffer, &json).unwrap();
+
+        assert_eq!(read_to_string(path).unwrap(), String::from_utf8(buffer).unwrap());
+    }
+
+    #[test]
+    fn can_open_and_read_xmp() {
+        let xmp = read_xmp(open_reader(Path::new(EXAMPLE_XMP_PATH)).unwrap()).unwrap();
+        let json = read_json(open_reader(Path::new(EXAMPLE_JSON_LD_PATH)).unwrap()).unwrap();
+
+        assert_eq!(xmp.packets, json.packets);
+    }
+
     #[test]
     fn can_open_and_read_gltf() {
-        let path = Path::new(SCIFI_HELMET_PATH);
+        let path = Path::new(BOX_PATH);
         let reader = open_reader(path);
         assert!(reader.is_ok());
         let gltf = read_gltf(reader.unwrap());
         assert!(gltf.is_ok());
     }
+
+    #[test]
+    fn can_round_trip_glb() {
+        let (gltf, bin) = read_glb(open_reader(Path::new(BOX_GLB_PATH)).unwrap()).unwrap();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write_glb(&mut buffer, &gltf, bin.clone()).unwrap();
+
+        let (_, round_tripped_bin) = read_glb(buffer.as_slice()).unwrap();
+        assert_eq!(bin, round_tripped_bin);
+    }
 }
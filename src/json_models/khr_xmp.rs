@@ -5,6 +5,8 @@
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
 
+pub const EXTENSION_NAME: &str = "KHR_xmp";
+
 #[derive(Serialize, Deserialize)]
 pub struct KhrXmpPacket {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -6,6 +6,8 @@ use serde::{Serialize, Deserialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
+pub const EXTENSION_NAME: &str = "KHR_xmp_json_ld";
+
 #[derive(Serialize, Deserialize)]
 pub struct KhrXmpJsonLdPacket {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -13,6 +15,7 @@ pub struct KhrXmpJsonLdPacket {
 }
 
 #[derive(Serialize, Deserialize)]
+#[allow(dead_code)]
 pub struct KhrXmpJsonLdPacketList {
     #[serde(rename = "@context")]
     pub context: Value,
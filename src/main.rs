@@ -7,70 +7,147 @@ extern crate gltf;
 extern crate serde;
 extern crate serde_json;
 
-use crate::io_helpers::{open_reader, open_writer, read_gltf, write_gltf, read_legacy_json, read_json};
+use crate::diff::{diff_metadata, Metadata, ObjectMatch};
+use crate::io_helpers::{create_temporary_file, is_stdio, open_reader, open_writer, replace_file, read_gltf, sniff_format, InputType, write_gltf, read_legacy_json, read_json, read_xliff, read_xmp, read_glb, write_glb, write_json, write_legacy_json, GlbContents};
 use crate::json_models::extension::{Extension};
+use crate::json_models::{khr_xmp, khr_xmp_json_ld};
 use crate::json_models::gltf::Gltf;
 use crate::json_models::khr_xmp::KhrXmp;
-use crate::managers::Manager;
+use crate::managers::{Manager, PacketTarget};
 use crate::managers::khr_xmp_manager::KhrXmpManager;
+use crate::managers::migration::{downgrade_to_legacy, migrate_to_json_ld};
+use crate::managers::packets::{collect_garbage, remove_packet};
+use crate::managers::update::{combine_contexts, combine_packets, UpdateMode};
 use clap::{App, Arg};
 use gltf::Glb;
-use std::borrow::Cow;
 use std::error::Error;
 use std::ffi::OsStr;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use crate::json_models::khr_xmp_json_ld::KhrXmpJsonLd;
 use crate::managers::khr_xmp_json_ld_manager::KhrXmpJsonLdManager;
-
+use crate::selectors::{parse_object_selector, parse_selector, resolve_objects, resolve_selectors, Selector};
+use crate::validation::integrity::check_references;
+use crate::validation::languages::check_languages;
+use crate::validation::packets::check_packets;
+use crate::validation::values::check_values;
+use crate::xliff::{apply_translations, collect_units, write_xliff};
+use crate::xmp::alternatives::{parse_language, remove_language, set_default_language, set_language, LanguageChange};
+use crate::xmp::language::{canonicalize_languages, DEFAULT_LANGUAGE};
+use crate::xmp::namespaces;
+use crate::xmp::normalize::normalize_packet;
+use crate::xmp::writer::write_xmp;
+
+mod diff;
 mod io_helpers;
 mod json_models;
 mod managers;
+mod references;
+mod selectors;
+mod validation;
+mod xliff;
+mod xmp;
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const ABOUT: &str = env!("CARGO_PKG_DESCRIPTION");
-const USAGE: &str = "gltfxmp [FLAGS] -j <JSON_FILE> -i <IN_FILE> -o <OUT_FILE>";
+const USAGE: &str = "gltfxmp [FLAGS] (-j <JSON_FILE> | -x <XMP_FILE>) -i <IN_FILE> -o <OUT_FILE>";
 
 const NO_METADATA_FOUND_ERROR: &str = "No metadata found.";
+const DEFAULT_BACKUP_SUFFIX: &str = ".bak";
 
-enum InputType {
-    Gltf,
-    Glb,
-}
 
 enum MetadataInputMode {
-    JSON(String),
-    XMP(String),
+    Json(String),
+    Xmp(String),
+    #[allow(dead_code)]
     Manual,
 }
 
-pub enum PacketApplied {
-    Asset(u64),
-    Animations(u64),
-    Images(u64),
-    Materials(u64),
-    Meshes(u64),
-    Nodes(u64),
-    Scenes(u64),
+/// A packet to be referenced by a single object.
+pub struct PacketApplied {
+    pub packet: u64,
+    pub target: PacketTarget,
+}
+
+/// Steps run on the packets before they are written.
+struct PacketOptions {
+    normalize: bool,
+    canonicalize_languages: bool,
+    /// Whether every Lang Alt must have an `x-default` item.
+    require_default_language: bool,
+}
+
+/// Changes to language alternatives, applied in this order.
+struct LanguageEdits {
+    /// Property, language and value.
+    set: Vec<(String, String, String)>,
+    /// Property and language.
+    remove: Vec<(String, String)>,
+    /// Property and language.
+    default: Vec<(String, String)>,
+}
+
+/// The packets that an edit applies to.
+enum PacketChoice {
+    Index(usize),
+    /// The packets applied to the selected objects.
+    Objects(Selector),
+}
+
+struct ExtractXmpOptions {
+    is_legacy: bool,
+    split: bool,
+    padding: usize,
+    allow_overwrite: bool,
 }
 
 #[derive(PartialEq)]
+#[allow(dead_code)]
 enum ExitCode {
     Normal = 0,
     Error = 1,
+    Changed = 2,
     Warn = 1000,
 }
 
 // TODO: Further reduce the number of unwraps to increase safety.
 // TODO: Need unit tests. A lot of unit tests.
 
-/// Performs simple extension validation on a input path.
-fn validate_file_as_extension(path: &Path, extension: &str) -> bool {
-    match path.extension() {
-        Some(ext) => ext.eq(OsStr::new(extension)),
-        None => false,
+/// Determines the format of the input file. An explicit `--input-format` wins; otherwise the
+/// format is detected from the content, and the file extension is only used as a hint when the
+/// content is neither GLB nor JSON.
+fn detect_input_type(path: &Path, format: Option<&str>, is_verbose: bool) -> Result<InputType, Box<dyn Error>> {
+    if let Some(format) = format {
+        return InputType::from_name(format).ok_or_else(|| format!("Unknown input format {}.", format).into());
     }
+
+    let hint = InputType::from_extension(path);
+    let reader = open_reader(path)
+        .map_err(|e| format!("The input file provided, {}, does not exist or is inaccessible: {}", path.display(), e))?;
+    let input_type = match (sniff_format(reader)?, hint) {
+        (Some(detected), _) => detected,
+        (None, Some(hint)) => hint,
+        (None, None) => {
+            return Err(format!(
+                "Unable to detect the format of {}. It is neither a GLB file nor a JSON document. Use --input-format to set it.",
+                path.display()
+            )
+            .into())
+        }
+    };
+
+    if hint.is_some_and(|h| h != input_type) {
+        log_if_verbose(
+            is_verbose,
+            format!("{} holds {} content despite its extension.", path.display(), input_type.name()).as_str(),
+        );
+    } else {
+        log_if_verbose(is_verbose, format!("Reading {} as {}.", path.display(), input_type.name()).as_str());
+    }
+
+    Ok(input_type)
 }
 
 fn get_manager(g: Gltf, is_legacy: bool) -> Box<dyn Manager> {
@@ -81,6 +158,35 @@ fn get_manager(g: Gltf, is_legacy: bool) -> Box<dyn Manager> {
     }
 }
 
+/// Returns the manager for the metadata extension a document actually holds, so that documents on
+/// either side of a migration can be compared. `--legacy` decides when it holds both or neither.
+fn get_detected_manager(g: Gltf, is_legacy: bool) -> Box<dyn Manager> {
+    let extensions = g.extensions.as_ref();
+    let has_legacy = extensions.is_some_and(|e| e.khr_xmp.is_some());
+    let has_json_ld = extensions.is_some_and(|e| e.khr_xmp_json_ld.is_some());
+    if has_legacy != has_json_ld {
+        get_manager(g, has_legacy)
+    } else {
+        get_manager(g, is_legacy)
+    }
+}
+
+/// Runs an edit against the metadata of a glTF document using the manager for its extension.
+fn edit_metadata<F>(gltf: Gltf, is_legacy: bool, edit: F) -> Result<Gltf, String>
+where
+    F: FnOnce(&mut dyn Manager) -> Result<(), String>,
+{
+    if is_legacy {
+        let mut manager = KhrXmpManager::new(gltf);
+        edit(&mut manager)?;
+        Ok(manager.into_gltf())
+    } else {
+        let mut manager = KhrXmpJsonLdManager::new(gltf);
+        edit(&mut manager)?;
+        Ok(manager.into_gltf())
+    }
+}
+
 fn list_gltf_metadata(path: &Path, is_legacy: bool) -> Result<(), String> {
     let reader = open_reader(path);
     let gltf = match reader {
@@ -117,9 +223,148 @@ fn list_glb_metadata(path: &Path, is_legacy: bool) -> Result<(), String> {
     }
 }
 
+/// Resolves the `--apply` selectors against the manager's glTF and applies the packets. Selector
+/// packet indices refer to the new metadata, so they are shifted by `offset` when appending.
+/// Name pattern and subtree selectors report how many objects they matched, and `+deps`
+/// selectors also list the expanded objects.
+fn apply_selectors<M: Manager>(
+    manager: &mut M,
+    selectors: &[Selector],
+    offset: u64,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    for selector in selectors.iter().filter(|s| s.reports_matches()) {
+        let matched = resolve_objects(selector, manager.get_gltf())?;
+        eprintln!("Selector \"{}\" matched {} object(s).", selector, matched.len());
+
+        if selector.dependencies {
+            let names: Vec<String> = matched.iter().map(|t| t.to_string()).collect();
+            eprintln!("\t{}", names.join(", "));
+        }
+    }
+
+    let mut apply_to = resolve_selectors(selectors, manager.get_gltf())?;
+    for applied in apply_to.iter_mut() {
+        applied.packet += offset;
+    }
+    for applied in &apply_to {
+        log_if_verbose(
+            is_verbose,
+            format!("Applying packet {} to {}", applied.packet, applied.target).as_str(),
+        );
+    }
+    manager.set_applied_packets(apply_to)?;
+
+    Ok(())
+}
+
+/// Rewrites standard property values into the JSON-LD form of their XMP container and returns
+/// how many were rewritten. Each rewritten value is reported if `report` is set.
+fn normalize_packets(manager: &mut dyn Manager, report: bool) -> Result<usize, String> {
+    let mut count = 0;
+    for (index, packet) in manager.packets_mut()?.iter_mut().enumerate() {
+        for normalized in normalize_packet(packet) {
+            log_if_verbose(
+                report,
+                format!(
+                    "Normalized {} in packet {} to {}.",
+                    normalized.path, index, normalized.container
+                )
+                    .as_str(),
+            );
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Canonicalizes the case of the language tags in the packets and returns how many were changed.
+/// Each change is reported if `report` is set.
+fn canonicalize_packet_languages(manager: &mut dyn Manager, report: bool) -> Result<usize, String> {
+    let mut count = 0;
+    for (index, packet) in manager.packets_mut()?.iter_mut().enumerate() {
+        for (tag, canonical) in canonicalize_languages(packet) {
+            log_if_verbose(
+                report,
+                format!("Canonicalized \"{}\" to \"{}\" in packet {}.", tag, canonical, index).as_str(),
+            );
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Runs the optional rewriting steps, completes the contexts and validates the packets before
+/// they are written. Completing the contexts creates any missing packet `@context`, so a packet
+/// without one is never rejected here; remote and malformed contexts still are.
+fn prepare_packets(manager: &mut dyn Manager, options: &PacketOptions, is_verbose: bool) -> Result<(), Box<dyn Error>> {
+    if options.normalize {
+        log_if_verbose(is_verbose, "Normalizing container types.");
+        normalize_packets(manager, is_verbose)?;
+    }
+    if options.canonicalize_languages {
+        log_if_verbose(is_verbose, "Canonicalizing language tags.");
+        canonicalize_packet_languages(manager, is_verbose)?;
+    }
+    complete_contexts(manager, is_verbose)?;
+    log_if_verbose(is_verbose, "Validating packets.");
+    validate_packets(manager, options)
+}
+
+/// Declares standard XMP prefixes missing from the packet contexts, warning about prefixes that
+/// are unknown or bound to a non-standard IRI.
+fn complete_contexts(manager: &mut dyn Manager, is_verbose: bool) -> Result<(), Box<dyn Error>> {
+    for (label, report) in manager.complete_contexts()? {
+        for prefix in &report.added {
+            log_if_verbose(
+                is_verbose,
+                format!("Declared the standard prefix \"{}\" in {}.", prefix, label).as_str(),
+            );
+        }
+        for prefix in &report.unknown {
+            eprintln!("Warning: {} uses the unknown prefix \"{}\". Declare it in the @context.", label, prefix);
+        }
+        for prefix in &report.mismatched {
+            eprintln!(
+                "Warning: {} binds the standard prefix \"{}\" to a non-standard IRI.",
+                label, prefix
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Fails with every problem found if the packets break the `KHR_xmp_json_ld` rules, and warns
+/// about values that do not match their XMP value type and about language tag problems. Legacy
+/// packets are checked with the global `@context` inlined. Expects the contexts to have been
+/// completed, so only `--validate` reports a packet without an `@context`.
+fn validate_packets(manager: &dyn Manager, options: &PacketOptions) -> Result<(), Box<dyn Error>> {
+    let packets = manager.get_packets()?;
+
+    // XMP value types and language tags are not part of the extension rules, so they do not
+    // block writing.
+    let warnings = check_values(&packets, "/packets")
+        .into_iter()
+        .chain(check_languages(&packets, "/packets", options.require_default_language));
+    for finding in warnings {
+        eprintln!("Warning: {}", finding);
+    }
+
+    let findings = check_packets(&packets, "/packets");
+    if findings.is_empty() {
+        return Ok(());
+    }
+
+    let lines: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+    Err(format!("The metadata is not valid:\n{}", lines.join("\n")).into())
+}
+
 fn log_if_verbose(verbose: bool, message: &str) {
     if verbose {
-        println!("{}", message);
+        eprintln!("{}", message);
     }
 }
 
@@ -129,7 +374,9 @@ fn update_gltf(
     input_path: &Path,
     output_path: &Path,
     metadata: &KhrXmpJsonLd,
-    apply_to: Vec<PacketApplied>,
+    selectors: &[Selector],
+    mode: UpdateMode,
+    options: &PacketOptions,
     is_verbose: bool,
 ) -> Result<(), Box<dyn Error>> {
     log_if_verbose(
@@ -144,10 +391,10 @@ fn update_gltf(
     let mut gltf = read_gltf(input_reader)?;
 
     let cloned = metadata.clone();
+    let mut offset = 0;
     if let Some(extensions) = gltf.extensions.as_mut() {
         if let Some(xmp) = extensions.khr_xmp_json_ld.as_mut() {
-            // TODO: Validation to make sure the input file isn't incorrect.
-            xmp.packets = cloned.packets;
+            offset = combine_packets(mode, &mut xmp.packets, cloned.packets)?;
         } else {
             extensions.khr_xmp_json_ld = Some(KhrXmpJsonLd {
                 packets: cloned.packets
@@ -173,10 +420,13 @@ fn update_gltf(
 
     let mut manager = KhrXmpJsonLdManager::new(gltf);
 
-    log_if_verbose(is_verbose, "Clearing all applied packets.");
-    manager.clear_applied_packets();
+    if mode == UpdateMode::Replace {
+        log_if_verbose(is_verbose, "Clearing all applied packets.");
+        manager.clear_applied_packets();
+    }
     log_if_verbose(is_verbose, "Setting new packets.");
-    manager.set_applied_packets(apply_to);
+    apply_selectors(&mut manager, selectors, offset, is_verbose)?;
+    prepare_packets(&mut manager, options, is_verbose)?;
 
     log_if_verbose(
         is_verbose,
@@ -197,7 +447,9 @@ fn update_glb(
     input_path: &Path,
     output_path: &Path,
     metadata: &KhrXmpJsonLd,
-    apply_to: Vec<PacketApplied>,
+    selectors: &[Selector],
+    mode: UpdateMode,
+    options: &PacketOptions,
     is_verbose: bool,
 ) -> Result<(), Box<dyn Error>> {
     log_if_verbose(
@@ -209,19 +461,14 @@ fn update_glb(
             .as_str(),
     );
     let input_reader = open_reader(input_path)?;
-    let glb = match Glb::from_reader(input_reader) {
-        Ok(g) => g,
-        Err(e) => return Err(e.into()),
-    };
-
-    let mut gltf: Gltf = serde_json::from_slice(glb.json.as_ref())?;
+    let (mut gltf, bin) = read_glb(input_reader)?;
 
     let cloned = metadata.clone();
+    let mut offset = 0;
 
     if let Some(extensions) = gltf.extensions.as_mut() {
         if let Some(xmp) = extensions.khr_xmp_json_ld.as_mut() {
-            // TODO: Validation to make sure the input file isn't incorrect.
-            xmp.packets = cloned.packets;
+            offset = combine_packets(mode, &mut xmp.packets, cloned.packets)?;
         } else {
             extensions.khr_xmp_json_ld = Some(KhrXmpJsonLd {
                 packets: cloned.packets
@@ -247,29 +494,16 @@ fn update_glb(
 
     let mut manager = KhrXmpJsonLdManager::new(gltf);
 
-    log_if_verbose(is_verbose, "Clearing all applied packets.");
-    manager.clear_applied_packets();
+    if mode == UpdateMode::Replace {
+        log_if_verbose(is_verbose, "Clearing all applied packets.");
+        manager.clear_applied_packets();
+    }
     log_if_verbose(is_verbose, "Setting new packets.");
-    manager.set_applied_packets(apply_to);
-
-    let json_data = serde_json::to_string_pretty(manager.get_gltf())?;
-    let json_offset = align_to_multiple_of_four(glb.json.len() as u32);
+    apply_selectors(&mut manager, selectors, offset, is_verbose)?;
+    prepare_packets(&mut manager, options, is_verbose)?;
 
-    let new_bin = glb.bin.unwrap_or_default().clone();
-    let new_glb = gltf::binary::Glb {
-        header: gltf::binary::Header {
-            magic: b"glTF".clone(),
-            version: 2,
-            length: json_offset + new_bin.len() as u32,
-        },
-        json: Cow::Owned(json_data.into_bytes()),
-        bin: Some(new_bin),
-    };
-
-    let writer = std::fs::File::create(output_path)?;
-    new_glb.to_writer(writer)?;
-
-    Ok(())
+    let output_writer = open_writer(output_path)?;
+    write_glb(output_writer, manager.get_gltf(), bin)
 }
 
 // TODO: Probably can find a better way to handle updating using traits. I need to clean up this duplicate code.
@@ -278,7 +512,9 @@ fn update_gltf_legacy(
     input_path: &Path,
     output_path: &Path,
     metadata: &KhrXmp,
-    apply_to: Vec<PacketApplied>,
+    selectors: &[Selector],
+    mode: UpdateMode,
+    options: &PacketOptions,
     is_verbose: bool,
 ) -> Result<(), Box<dyn Error>> {
     log_if_verbose(
@@ -293,11 +529,11 @@ fn update_gltf_legacy(
     let mut gltf = read_gltf(input_reader)?;
 
     let cloned = metadata.clone();
+    let mut offset = 0;
     if let Some(extensions) = gltf.extensions.as_mut() {
         if let Some(xmp) = extensions.khr_xmp.as_mut() {
-            // TODO: Validation to make sure the input file isn't incorrect.
-            xmp.context = cloned.context;
-            xmp.packets = cloned.packets;
+            combine_contexts(mode, &mut xmp.context, cloned.context)?;
+            offset = combine_packets(mode, &mut xmp.packets, cloned.packets)?;
         } else {
             extensions.khr_xmp = Some(KhrXmp {
                 context: cloned.context,
@@ -325,10 +561,13 @@ fn update_gltf_legacy(
 
     let mut manager = KhrXmpManager::new(gltf);
 
-    log_if_verbose(is_verbose, "Clearing all applied packets.");
-    manager.clear_applied_packets();
+    if mode == UpdateMode::Replace {
+        log_if_verbose(is_verbose, "Clearing all applied packets.");
+        manager.clear_applied_packets();
+    }
     log_if_verbose(is_verbose, "Setting new packets.");
-    manager.set_applied_packets(apply_to);
+    apply_selectors(&mut manager, selectors, offset, is_verbose)?;
+    prepare_packets(&mut manager, options, is_verbose)?;
 
     log_if_verbose(
         is_verbose,
@@ -349,7 +588,9 @@ fn update_glb_legacy(
     input_path: &Path,
     output_path: &Path,
     metadata: &KhrXmp,
-    apply_to: Vec<PacketApplied>,
+    selectors: &[Selector],
+    mode: UpdateMode,
+    options: &PacketOptions,
     is_verbose: bool,
 ) -> Result<(), Box<dyn Error>> {
     log_if_verbose(
@@ -361,20 +602,15 @@ fn update_glb_legacy(
             .as_str(),
     );
     let input_reader = open_reader(input_path)?;
-    let glb = match Glb::from_reader(input_reader) {
-        Ok(g) => g,
-        Err(e) => return Err(e.into()),
-    };
-
-    let mut gltf: Gltf = serde_json::from_slice(glb.json.as_ref())?;
+    let (mut gltf, bin) = read_glb(input_reader)?;
 
     let cloned = metadata.clone();
+    let mut offset = 0;
 
     if let Some(extensions) = gltf.extensions.as_mut() {
         if let Some(xmp) = extensions.khr_xmp.as_mut() {
-            // TODO: Validation to make sure the input file isn't incorrect.
-            xmp.context = cloned.context;
-            xmp.packets = cloned.packets;
+            combine_contexts(mode, &mut xmp.context, cloned.context)?;
+            offset = combine_packets(mode, &mut xmp.packets, cloned.packets)?;
         } else {
             extensions.khr_xmp = Some(KhrXmp {
                 context: cloned.context,
@@ -402,33 +638,553 @@ fn update_glb_legacy(
 
     let mut manager = KhrXmpManager::new(gltf);
 
-    log_if_verbose(is_verbose, "Clearing all applied packets.");
-    manager.clear_applied_packets();
+    if mode == UpdateMode::Replace {
+        log_if_verbose(is_verbose, "Clearing all applied packets.");
+        manager.clear_applied_packets();
+    }
     log_if_verbose(is_verbose, "Setting new packets.");
-    manager.set_applied_packets(apply_to);
+    apply_selectors(&mut manager, selectors, offset, is_verbose)?;
+    prepare_packets(&mut manager, options, is_verbose)?;
+
+    let output_writer = open_writer(output_path)?;
+    write_glb(output_writer, manager.get_gltf(), bin)
+}
 
-    let json_data = serde_json::to_string_pretty(manager.get_gltf())?;
-    let json_offset = align_to_multiple_of_four(glb.json.len() as u32);
+fn align_to_multiple_of_four(n: u32) -> u32 {
+    (n + 3) & !3
+}
 
-    let new_bin = glb.bin.unwrap_or_default().clone();
-    let new_glb = gltf::binary::Glb {
-        header: gltf::binary::Header {
-            magic: b"glTF".clone(),
-            version: 2,
-            length: json_offset + new_bin.len() as u32,
-        },
-        json: Cow::Owned(json_data.into_bytes()),
-        bin: Some(new_bin),
+/// Reads the glTF JSON of the input file, along with the BIN chunk of binary glTF files.
+fn read_input(
+    input_path: &Path,
+    input_type: &InputType,
+    is_verbose: bool,
+) -> Result<GlbContents, Box<dyn Error>> {
+    log_if_verbose(
+        is_verbose,
+        format!(
+            "Opening & reading input file at path {}",
+            input_path.display()
+        )
+            .as_str(),
+    );
+    let input_reader = open_reader(input_path)?;
+    match input_type {
+        InputType::Gltf => Ok((read_gltf(input_reader)?, None)),
+        InputType::Glb => read_glb(input_reader),
+    }
+}
+
+/// Reads the input file, applies `transform` to its glTF JSON and writes the result to the output
+/// file. Binary glTF files keep their BIN chunk untouched.
+fn transform_file<F>(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    is_verbose: bool,
+    transform: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(Gltf) -> Result<Gltf, Box<dyn Error>>,
+{
+    let (gltf, bin) = read_input(input_path, input_type, is_verbose)?;
+
+    let gltf = transform(gltf)?;
+
+    log_if_verbose(
+        is_verbose,
+        format!(
+            "Opening & writing to output file at path {}",
+            output_path.display()
+        )
+            .as_str(),
+    );
+    let output_writer = open_writer(output_path)?;
+    match input_type {
+        InputType::Gltf => write_gltf(output_writer, &gltf),
+        InputType::Glb => write_glb(output_writer, &gltf, bin),
+    }
+}
+
+/// Runs `write_output` against a temporary file instead of the output and prints how the metadata
+/// would change. Returns whether there were any changes.
+fn dry_run<F>(
+    input_path: &Path,
+    input_type: &InputType,
+    is_legacy: bool,
+    is_verbose: bool,
+    write_output: F,
+) -> Result<bool, Box<dyn Error>>
+where
+    F: FnOnce(&Path) -> Result<(), Box<dyn Error>>,
+{
+    let temporary = create_temporary_file()?;
+    let output = write_output(&temporary).and_then(|_| read_input(&temporary, input_type, is_verbose));
+    let _ = std::fs::remove_file(&temporary);
+    let (after, _) = output?;
+    let (before, _) = read_input(input_path, input_type, is_verbose)?;
+
+    let diff = diff_metadata(
+        &Metadata::read(get_detected_manager(before, is_legacy).as_ref(), ObjectMatch::Index),
+        &Metadata::read(get_detected_manager(after, is_legacy).as_ref(), ObjectMatch::Index),
+    );
+    print!("{}", diff);
+
+    Ok(!diff.is_empty())
+}
+
+/// Compares the metadata of two files and prints the differences, as text or as JSON. Returns
+/// whether there were any.
+fn diff_files(
+    before_path: &Path,
+    after_path: &Path,
+    object_match: ObjectMatch,
+    is_json: bool,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let read = |path: &Path| -> Result<Metadata, Box<dyn Error>> {
+        let input_type = detect_input_type(path, None, is_verbose)?;
+        let (gltf, _) = read_input(path, &input_type, is_verbose)?;
+        Ok(Metadata::read(get_detected_manager(gltf, is_legacy).as_ref(), object_match))
+    };
+
+    let diff = diff_metadata(&read(before_path)?, &read(after_path)?);
+    if is_json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        print!("{}", diff);
+    }
+
+    Ok(!diff.is_empty())
+}
+
+/// Writes the metadata extension of the input file to a JSON file, in the same shape that `--json`
+/// accepts. Extracting and re-applying metadata is lossless.
+fn extract_json(
+    input_path: &Path,
+    input_type: &InputType,
+    json_path: &Path,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (gltf, _) = read_input(input_path, input_type, is_verbose)?;
+    let extensions = gltf.extensions.as_ref();
+
+    log_if_verbose(
+        is_verbose,
+        format!("Writing metadata to {}", json_path.display()).as_str(),
+    );
+    if is_legacy {
+        match extensions.and_then(|e| e.khr_xmp.as_ref()) {
+            Some(xmp) => write_legacy_json(open_writer(json_path)?, xmp),
+            None => Err(NO_METADATA_FOUND_ERROR.into()),
+        }
+    } else {
+        match extensions.and_then(|e| e.khr_xmp_json_ld.as_ref()) {
+            Some(xmp) => write_json(open_writer(json_path)?, xmp),
+            None => Err(NO_METADATA_FOUND_ERROR.into()),
+        }
+    }
+}
+
+/// Exports the language alternatives of the input file that have an entry in `source_language`
+/// as XLIFF translation units.
+fn export_xliff(
+    input_path: &Path,
+    input_type: &InputType,
+    xliff_path: &Path,
+    source_language: &str,
+    target_language: Option<&str>,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let source_language = parse_language(source_language)?;
+    let target_language = target_language.map(parse_language).transpose()?;
+
+    let (gltf, _) = read_input(input_path, input_type, is_verbose)?;
+    let packets = get_manager(gltf, is_legacy).get_packets()?;
+    let units = collect_units(&packets, &source_language, target_language.as_deref());
+
+    let original = input_path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+    let xliff = write_xliff(&units, original, &source_language, target_language.as_deref());
+
+    log_if_verbose(
+        is_verbose,
+        format!("Writing XLIFF to {}", xliff_path.display()).as_str(),
+    );
+    let mut writer = open_writer(xliff_path)?;
+    writer.write_all(xliff.as_bytes())?;
+    writer.flush()?;
+    eprintln!("Exported {} translation unit(s).", units.len());
+
+    Ok(())
+}
+
+/// Writes the translated targets of an XLIFF file into the language alternatives they were
+/// exported from.
+fn import_xliff(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    xliff_path: &Path,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    log_if_verbose(
+        is_verbose,
+        format!("Reading XLIFF from {}", xliff_path.display()).as_str(),
+    );
+    let translations = read_xliff(open_reader(xliff_path)?)?;
+
+    transform_file(input_path, output_path, input_type, is_verbose, |gltf| {
+        Ok(edit_metadata(gltf, is_legacy, |manager| {
+            let changes = apply_translations(manager.packets_mut()?, &translations)?;
+            let added = changes.iter().filter(|c| **c == LanguageChange::Added).count();
+            eprintln!(
+                "Added {} and replaced {} translation(s).",
+                added,
+                changes.len() - added
+            );
+            Ok(())
+        })?)
+    })
+}
+
+/// Returns the path of the sidecar file for a single packet, e.g. `metadata.2.xmp`.
+fn packet_sidecar_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}.{}.{}", stem, index, ext.to_string_lossy())),
+        None => path.with_file_name(format!("{}.{}", stem, index)),
+    }
+}
+
+/// Writes the metadata packets of the input file as XMP (RDF/XML), either as one combined file or
+/// as one sidecar file per packet.
+fn extract_xmp(
+    input_path: &Path,
+    input_type: &InputType,
+    xmp_path: &Path,
+    options: &ExtractXmpOptions,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (gltf, _) = read_input(input_path, input_type, is_verbose)?;
+    let packets = get_manager(gltf, options.is_legacy).get_packets()?;
+
+    let files = if options.split {
+        packets
+            .iter()
+            .enumerate()
+            .map(|(index, packet)| {
+                let xmp = write_xmp(std::slice::from_ref(packet), options.padding)?;
+                Ok((packet_sidecar_path(xmp_path, index), xmp))
+            })
+            .collect::<Result<Vec<(PathBuf, String)>, String>>()?
+    } else {
+        vec![(xmp_path.to_path_buf(), write_xmp(&packets, options.padding)?)]
     };
 
-    let writer = std::fs::File::create(output_path)?;
-    new_glb.to_writer(writer)?;
+    for (path, _) in &files {
+        if path.exists() && !options.allow_overwrite {
+            return Err(format!(
+                "The XMP output path provided {}, already exists. Use the --allow-overwrite flag to allow overwriting.",
+                path.display()
+            )
+            .into());
+        }
+    }
+
+    for (path, xmp) in files {
+        log_if_verbose(
+            is_verbose,
+            format!("Writing XMP packet to {}", path.display()).as_str(),
+        );
+        let mut writer = open_writer(&path)?;
+        writer.write_all(xmp.as_bytes())?;
+        writer.flush()?;
+    }
 
     Ok(())
 }
 
-fn align_to_multiple_of_four(n: u32) -> u32 {
-    return (n + 3) & !3;
+/// Migrates the `KHR_xmp` metadata in the input file to `KHR_xmp_json_ld`.
+fn migrate(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    transform_file(input_path, output_path, input_type, is_verbose, |gltf| {
+        log_if_verbose(is_verbose, "Migrating KHR_xmp metadata to KHR_xmp_json_ld.");
+        Ok(migrate_to_json_ld(gltf)?)
+    })
+}
+
+/// Converts the `KHR_xmp_json_ld` metadata in the input file back to legacy `KHR_xmp`, reporting
+/// any prefixes that could not be merged into the global `@context`.
+fn downgrade(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    transform_file(input_path, output_path, input_type, is_verbose, |gltf| {
+        log_if_verbose(is_verbose, "Downgrading KHR_xmp_json_ld metadata to KHR_xmp.");
+        let (gltf, collisions) = downgrade_to_legacy(gltf)?;
+        for collision in collisions {
+            eprintln!("Warning: {}", collision);
+        }
+        Ok(gltf)
+    })
+}
+
+/// Removes a packet from the input file, pointing the objects that referenced it at
+/// `reassign_to` or dropping their references, and renumbers the references to later packets.
+fn remove(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    packet: usize,
+    reassign_to: Option<usize>,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    transform_file(input_path, output_path, input_type, is_verbose, |gltf| {
+        log_if_verbose(is_verbose, format!("Removing packet {}.", packet).as_str());
+        Ok(edit_metadata(gltf, is_legacy, |manager| {
+            let affected = remove_packet(manager, packet, reassign_to)?;
+            for target in &affected {
+                match reassign_to {
+                    Some(m) => eprintln!("Reassigned {} from packet {} to packet {}.", target, packet, m),
+                    None => eprintln!("Dropped the reference from {} to packet {}.", target, packet),
+                }
+            }
+            Ok(())
+        })?)
+    })
+}
+
+/// Resolves a `PacketChoice` to packet indices, in order and without duplicates.
+fn choose_packets(manager: &dyn Manager, choice: &PacketChoice) -> Result<Vec<usize>, String> {
+    let selector = match choice {
+        PacketChoice::Index(index) => return Ok(vec![*index]),
+        PacketChoice::Objects(selector) => selector,
+    };
+
+    let targets = resolve_objects(selector, manager.get_gltf())?;
+    let mut packets: Vec<usize> = manager
+        .get_packet_references()
+        .into_iter()
+        .filter(|(target, _)| targets.contains(target))
+        .map(|(_, packet)| packet as usize)
+        .collect();
+    packets.sort_unstable();
+    packets.dedup();
+
+    if packets.is_empty() {
+        return Err(format!(
+            "No packet is applied to \"{}\". Use --packet to choose one by index.",
+            selector
+        ));
+    }
+    Ok(packets)
+}
+
+/// Adds, replaces and removes the entries of language alternatives in the chosen packets,
+/// keeping their `rdf:_N` keys contiguous.
+fn edit_languages(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    edits: &LanguageEdits,
+    choice: &PacketChoice,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    transform_file(input_path, output_path, input_type, is_verbose, |gltf| {
+        Ok(edit_metadata(gltf, is_legacy, |manager| {
+            let chosen = choose_packets(manager, choice)?;
+            let packets = manager.packets_mut()?;
+            let count = packets.len();
+
+            for index in chosen {
+                let packet = packets.get_mut(index).ok_or_else(|| {
+                    format!("Packet {} does not exist. The metadata only contains {} packet(s).", index, count)
+                })?;
+
+                for (property, language, value) in &edits.set {
+                    match set_language(packet, property, language, value)? {
+                        LanguageChange::Added => eprintln!("Added {} in {} to packet {}.", property, language, index),
+                        LanguageChange::Replaced => eprintln!("Replaced {} in {} in packet {}.", property, language, index),
+                    }
+                }
+                for (property, language) in &edits.remove {
+                    if remove_language(packet, property, language)? {
+                        eprintln!("Removed {} in {} from packet {}.", property, language, index);
+                    } else {
+                        eprintln!("Warning: packet {} has no {} entry in {}.", index, property, language);
+                    }
+                }
+                for (property, language) in &edits.default {
+                    set_default_language(packet, property, language)?;
+                    eprintln!("Made {} the default language of {} in packet {}.", language, property, index);
+                }
+            }
+
+            // New language alternatives use the rdf prefix.
+            complete_contexts(manager, is_verbose).map_err(|e| e.to_string())
+        })?)
+    })
+}
+
+/// Removes the unreferenced and duplicate packets of the input file and reports how many bytes
+/// the JSON chunk shrank by.
+fn garbage_collect(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    // The size of the JSON chunk as written, including GLB padding.
+    let json_size = |gltf: &Gltf| -> Result<usize, serde_json::Error> {
+        let length = serde_json::to_string_pretty(gltf)?.len();
+        Ok(match input_type {
+            InputType::Gltf => length,
+            InputType::Glb => align_to_multiple_of_four(length as u32) as usize,
+        })
+    };
+
+    transform_file(input_path, output_path, input_type, is_verbose, |gltf| {
+        let size_before = json_size(&gltf)?;
+
+        log_if_verbose(is_verbose, "Removing unreferenced and duplicate packets.");
+        let gltf = edit_metadata(gltf, is_legacy, |manager| {
+            let report = collect_garbage(manager)?;
+            for packet in &report.unreferenced {
+                eprintln!("Removed unreferenced packet {}.", packet);
+            }
+            for (packet, survivor) in &report.duplicates {
+                eprintln!("Merged packet {} into identical packet {}.", packet, survivor);
+            }
+            for target in &report.dangling {
+                eprintln!("Dropped the reference from {} to a packet that does not exist.", target);
+            }
+            Ok(())
+        })?;
+
+        let size_after = json_size(&gltf)?;
+        eprintln!(
+            "Saved {} bytes in the JSON chunk.",
+            size_before.saturating_sub(size_after)
+        );
+
+        Ok(gltf)
+    })
+}
+
+/// Normalizes the container types and canonicalizes the language tags of the metadata in the
+/// input file, as selected by `options`.
+fn normalize(
+    input_path: &Path,
+    output_path: &Path,
+    input_type: &InputType,
+    options: &PacketOptions,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    transform_file(input_path, output_path, input_type, is_verbose, |gltf| {
+        Ok(edit_metadata(gltf, is_legacy, |manager| {
+            if options.normalize {
+                log_if_verbose(is_verbose, "Normalizing container types.");
+                let count = normalize_packets(manager, true)?;
+                // Alt containers use the rdf prefix.
+                complete_contexts(manager, is_verbose).map_err(|e| e.to_string())?;
+                eprintln!("Normalized {} value(s).", count);
+            }
+            if options.canonicalize_languages {
+                log_if_verbose(is_verbose, "Canonicalizing language tags.");
+                let count = canonicalize_packet_languages(manager, true)?;
+                eprintln!("Canonicalized {} language tag(s).", count);
+            }
+            Ok(())
+        })?)
+    })
+}
+
+/// Prints every problem found in the input file and returns how many there were.
+fn validate(
+    input_path: &Path,
+    input_type: &InputType,
+    require_default_language: bool,
+    is_legacy: bool,
+    is_verbose: bool,
+) -> Result<usize, Box<dyn Error>> {
+    let (gltf, _) = read_input(input_path, input_type, is_verbose)?;
+    let extension = if is_legacy {
+        khr_xmp::EXTENSION_NAME
+    } else {
+        khr_xmp_json_ld::EXTENSION_NAME
+    };
+
+    log_if_verbose(is_verbose, format!("Checking {} packet references.", extension).as_str());
+    let mut findings = check_references(&gltf, extension)?;
+
+    // Missing metadata is reported by the reference checks when it matters.
+    if let Ok(packets) = get_manager(gltf, is_legacy).get_packets() {
+        log_if_verbose(is_verbose, "Checking packets.");
+        let packets_path = format!("/extensions/{}/packets", extension);
+        findings.extend(check_packets(&packets, &packets_path));
+        log_if_verbose(is_verbose, "Checking property values.");
+        findings.extend(check_values(&packets, &packets_path));
+        log_if_verbose(is_verbose, "Checking language tags.");
+        findings.extend(check_languages(&packets, &packets_path, require_default_language));
+    }
+    for finding in &findings {
+        println!("{}", finding);
+    }
+
+    Ok(findings.len())
+}
+
+/// Prints the schema of a standard XMP property, e.g. `dc:rights`, or the properties of a
+/// namespace when given only a prefix, e.g. `dc`.
+fn describe(name: &str) -> Result<(), String> {
+    let (prefix, property) = match name.split_once(':') {
+        Some((prefix, property)) => (prefix, Some(property)),
+        None => (name, None),
+    };
+
+    let namespace = match namespaces::namespace(prefix) {
+        Some(n) => n,
+        None => {
+            let prefixes: Vec<&str> = namespaces::NAMESPACES.iter().map(|n| n.prefix).collect();
+            return Err(format!(
+                "\"{}\" is not a standard XMP prefix. Known prefixes are {}.",
+                prefix,
+                prefixes.join(", ")
+            ));
+        }
+    };
+
+    if property.is_none() {
+        println!("{}: {} ({})", namespace.prefix, namespace.name, namespace.iri);
+        for schema in namespaces::PROPERTIES.iter().filter(|p| p.name.starts_with(&format!("{}:", prefix))) {
+            println!("\t{}: {}", schema.name, schema.description);
+        }
+        return Ok(());
+    }
+
+    let schema = namespaces::property_schema(name)
+        .ok_or_else(|| format!("\"{}\" is not a known property of the {} namespace.", name, namespace.name))?;
+    println!("{}", schema.name);
+    println!("\tNamespace: {} ({})", namespace.name, namespace.iri);
+    println!("\tType: {}", schema.value_type);
+    println!("\tContainer: {}", schema.container);
+    println!("\t{}", schema.description);
+
+    Ok(())
 }
 
 /// Performs a graceful exit with the specified `ExitCode` and an optional message.
@@ -444,6 +1200,12 @@ fn clean_exit(code: ExitCode, message: Option<&str>) {
     exit(code as i32);
 }
 
+/// Groups the values of an argument that takes two values per occurrence.
+fn pairs(values: Option<clap::Values>) -> Vec<(String, String)> {
+    let values: Vec<&str> = values.map(|v| v.collect()).unwrap_or_default();
+    values.chunks(2).map(|c| (c[0].to_string(), c[1].to_string())).collect()
+}
+
 /// Performs a graceful exit, printing the error message contained in the error.
 fn exit_on_error(e: Box<dyn Error>) {
     clean_exit(ExitCode::Error, Some(e.to_string().as_str()))
@@ -460,7 +1222,15 @@ fn main() {
                 .long("input")
                 .value_name("IN_FILE")
                 .help("Input file path")
-                .required(true)
+                .required_unless_one(&["describe", "diff"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("input_format")
+                .long("input-format")
+                .value_name("FORMAT")
+                .help("Format of the input file, overriding detection from its content")
+                .possible_values(&["gltf", "glb"])
                 .takes_value(true),
         )
         .arg(
@@ -469,7 +1239,7 @@ fn main() {
                 .long("output")
                 .value_name("OUT_FILE")
                 .help("Output file path")
-                .required_unless("list")
+                .required_unless_one(&["list", "validate", "describe", "extract", "extract_xmp", "export_xliff", "in_place", "dry_run", "diff"])
                 .takes_value(true),
         )
         .arg(
@@ -484,19 +1254,61 @@ fn main() {
                 .long("json")
                 .value_name("JSON_FILE")
                 .help("Use raw JSON input file mode")
-                // .required_unless("xmp")
-                .required_unless("migrate")
-                .required_unless("list"), // .conflicts_with("xmp"),
-        )
-        // .arg(
-        //     Arg::with_name("xmp")
-        //         .short("x")
-        //         .long("xmp")
-        //         .help("Use XMP input file mode")
-        //         .required_unless("json")
-        //         .required_unless("list")
-        //         .conflicts_with("json"),
-        // )
+                .required_unless_one(&["xmp", "migrate", "downgrade", "remove_packet", "gc", "normalize", "canonicalize_languages", "set_lang", "remove_lang", "default_lang", "import_xliff", "validate", "describe", "list", "extract", "extract_xmp", "export_xliff", "diff"])
+                .conflicts_with("xmp"),
+        )
+        .arg(
+            Arg::with_name("xmp")
+                .short("x")
+                .long("xmp")
+                .value_name("XMP_FILE")
+                .help("Use XMP (RDF/XML) input file mode")
+                .conflicts_with_all(&["json", "legacy", "migrate", "downgrade"]),
+        )
+        .arg(
+            Arg::with_name("apply")
+                .long("apply")
+                .value_name("SELECTOR")
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["migrate", "downgrade"])
+                .help("Applies a packet to objects, e.g. packet=1:nodes[3,5]. Repeatable. Defaults to packet=0:asset"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .long("mode")
+                .value_name("MODE")
+                .possible_values(&["replace", "append", "merge"])
+                .conflicts_with_all(&["migrate", "downgrade"])
+                .help("How new metadata is combined with existing metadata: replace (default), append or merge. append and merge keep existing packet references and apply no packets unless --apply is given"),
+        )
+        .arg(
+            Arg::with_name("extract")
+                .long("extract")
+                .value_name("JSON_FILE")
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "extract_xmp"])
+                .help("Extracts the metadata to a JSON file accepted by --json"),
+        )
+        .arg(
+            Arg::with_name("extract_xmp")
+                .long("extract-xmp")
+                .value_name("XMP_FILE")
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade"])
+                .help("Extracts the metadata packets to an XMP (RDF/XML) file"),
+        )
+        .arg(
+            Arg::with_name("split_packets")
+                .long("split-packets")
+                .requires("extract_xmp")
+                .help("Writes one XMP sidecar file per packet, e.g. <XMP_FILE>.0.xmp"),
+        )
+        .arg(
+            Arg::with_name("padding")
+                .long("padding")
+                .value_name("BYTES")
+                .requires("extract_xmp")
+                .help("Whitespace padding to add to each XMP packet, allowing in-place edits"),
+        )
         .arg(
             Arg::with_name("legacy")
                 .long("legacy")
@@ -508,8 +1320,132 @@ fn main() {
             Arg::with_name("migrate")
                 .short("m")
                 .long("migrate")
+                .conflicts_with("json")
                 .help("Migrate KHR_xmp data to KHR_xmp_json_ld data")
         )
+        .arg(
+            Arg::with_name("downgrade")
+                .long("downgrade")
+                .conflicts_with_all(&["json", "migrate", "legacy"])
+                .help("Convert KHR_xmp_json_ld data back to legacy KHR_xmp data")
+        )
+        .arg(
+            Arg::with_name("remove_packet")
+                .long("remove-packet")
+                .value_name("N")
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "apply", "list", "extract", "extract_xmp"])
+                .help("Removes packet N and renumbers the references to later packets"),
+        )
+        .arg(
+            Arg::with_name("reassign_to")
+                .long("reassign-to")
+                .value_name("M")
+                .requires("remove_packet")
+                .help("With --remove-packet, points references to the removed packet at packet M instead of dropping them"),
+        )
+        .arg(
+            Arg::with_name("gc")
+                .long("gc")
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "apply", "remove_packet", "list", "extract", "extract_xmp"])
+                .help("Removes unreferenced and duplicate packets and compacts packet indices"),
+        )
+        .arg(
+            Arg::with_name("normalize")
+                .long("normalize")
+                .conflicts_with_all(&["migrate", "downgrade", "remove_packet", "gc", "list", "extract", "extract_xmp"])
+                .help("Rewrites standard properties into the JSON-LD form of their XMP container. Applies to new metadata with --json or --xmp, otherwise to the input file"),
+        )
+        .arg(
+            Arg::with_name("canonicalize_languages")
+                .long("canonicalize-languages")
+                .conflicts_with_all(&["migrate", "downgrade", "remove_packet", "gc", "list", "extract", "extract_xmp"])
+                .help("Rewrites @language tags in their canonical BCP 47 case, e.g. en-US. Applies to new metadata with --json or --xmp, otherwise to the input file"),
+        )
+        .arg(
+            Arg::with_name("require_x_default")
+                .long("require-x-default")
+                .help("Reports language alternatives without an x-default item"),
+        )
+        .arg(
+            Arg::with_name("set_lang")
+                .long("set-lang")
+                .value_names(&["PROPERTY", "LANG", "VALUE"])
+                .multiple(true)
+                .number_of_values(3)
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "apply", "remove_packet", "gc", "normalize", "canonicalize_languages", "validate", "list", "extract", "extract_xmp"])
+                .help("Adds or replaces the entry of a language alternative, e.g. --set-lang dc:title it-IT \"Mio Modello\". Repeatable"),
+        )
+        .arg(
+            Arg::with_name("remove_lang")
+                .long("remove-lang")
+                .value_names(&["PROPERTY", "LANG"])
+                .multiple(true)
+                .number_of_values(2)
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "apply", "remove_packet", "gc", "normalize", "canonicalize_languages", "validate", "list", "extract", "extract_xmp"])
+                .help("Removes the entry of a language alternative, e.g. --remove-lang dc:title fr-FR. Repeatable"),
+        )
+        .arg(
+            Arg::with_name("default_lang")
+                .long("default-lang")
+                .value_names(&["PROPERTY", "LANG"])
+                .multiple(true)
+                .number_of_values(2)
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "apply", "remove_packet", "gc", "normalize", "canonicalize_languages", "validate", "list", "extract", "extract_xmp"])
+                .help("Copies the entry of a language alternative to its first, x-default entry, e.g. --default-lang dc:title en-US. Repeatable"),
+        )
+        .arg(
+            Arg::with_name("packet")
+                .long("packet")
+                .value_name("N")
+                .conflicts_with("packet_of")
+                .help("With the language alternative edits, edits packet N"),
+        )
+        .arg(
+            Arg::with_name("packet_of")
+                .long("packet-of")
+                .value_name("OBJECTS")
+                .help("With the language alternative edits, edits the packets applied to the objects, e.g. nodes[3]. Defaults to asset"),
+        )
+        .arg(
+            Arg::with_name("export_xliff")
+                .long("export-xliff")
+                .value_name("XLIFF_FILE")
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "extract", "extract_xmp"])
+                .help("Exports every language alternative as an XLIFF 1.2 translation unit, keyed by packet index and property path"),
+        )
+        .arg(
+            Arg::with_name("source_lang")
+                .long("source-lang")
+                .value_name("LANG")
+                .requires("export_xliff")
+                .help("With --export-xliff, the language of the source text. Defaults to x-default"),
+        )
+        .arg(
+            Arg::with_name("target_lang")
+                .long("target-lang")
+                .value_name("LANG")
+                .requires("export_xliff")
+                .help("With --export-xliff, the language to translate to. Existing entries in it are exported as targets"),
+        )
+        .arg(
+            Arg::with_name("import_xliff")
+                .long("import-xliff")
+                .value_name("XLIFF_FILE")
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "apply", "remove_packet", "gc", "normalize", "canonicalize_languages", "set_lang", "remove_lang", "default_lang", "validate", "list", "extract", "extract_xmp", "export_xliff"])
+                .help("Writes the translated targets of an XLIFF file into the language alternatives they were exported from"),
+        )
+        .arg(
+            Arg::with_name("validate")
+                .long("validate")
+                .conflicts_with_all(&["json", "xmp", "migrate", "downgrade", "apply", "remove_packet", "gc", "normalize", "canonicalize_languages", "list", "extract", "extract_xmp"])
+                .help("Checks the metadata of the input file and reports each problem with its JSON path"),
+        )
+        .arg(
+            Arg::with_name("describe")
+                .long("describe")
+                .value_name("PROPERTY")
+                .help("Prints the expected type and container of a standard XMP property, e.g. dc:rights"),
+        )
         .arg(
             Arg::with_name("verbose")
                 .short("v")
@@ -522,6 +1458,52 @@ fn main() {
                 .long("allow-overwrite")
                 .help("Allows output file overwriting. Use at your own risk!"),
         )
+        .arg(
+            Arg::with_name("diff")
+                .long("diff")
+                .value_names(&["A", "B"])
+                .help("Prints how the metadata of file B differs from that of file A: packet properties, packet references and extensionsUsed entries. Exits with code 2 if they differ. Does not need an input file")
+                .number_of_values(2),
+        )
+        .arg(
+            Arg::with_name("match_by")
+                .long("match-by")
+                .value_name("KEY")
+                .help("How --diff matches the objects holding packet references: by index (the default) or by name")
+                .possible_values(&["index", "name"])
+                .requires("diff")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("diff_format")
+                .long("diff-format")
+                .value_name("FORMAT")
+                .help("Output format of --diff: text (the default) or json")
+                .possible_values(&["text", "json"])
+                .requires("diff")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .help("Runs the update without writing anything and prints how the metadata would change. Exits with code 2 if it would change"),
+        )
+        .arg(
+            Arg::with_name("in_place")
+                .long("in-place")
+                .help("Edits the input file in place, replacing it atomically once the output is complete")
+                .conflicts_with("output"),
+        )
+        .arg(
+            Arg::with_name("backup")
+                .long("backup")
+                .value_name("SUFFIX")
+                .help("Keeps the previous version of a file edited with --in-place, with a suffix appended (.bak by default)")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .requires("in_place"),
+        )
         .get_matches();
 
     // Check verbosity
@@ -532,13 +1514,51 @@ fn main() {
 
     // Check migration mode
     let migration = matches.is_present("migrate");
+    let downgrading = matches.is_present("downgrade");
+    let packet_options = PacketOptions {
+        normalize: matches.is_present("normalize"),
+        canonicalize_languages: matches.is_present("canonicalize_languages"),
+        require_default_language: matches.is_present("require_x_default"),
+    };
+
+    // clap only accepts the possible values, so parsing cannot fail here.
+    let update_mode = UpdateMode::parse(matches.value_of("mode").unwrap_or("replace")).unwrap();
+
+    let selectors = match matches.values_of("apply") {
+        Some(values) => match values.map(parse_selector).collect::<Result<Vec<Selector>, String>>() {
+            Ok(s) => s,
+            Err(e) => return clean_exit(ExitCode::Error, Some(e.as_str())),
+        },
+        // Appended and merged packets keep the references they already have, including the asset's.
+        None if update_mode != UpdateMode::Replace => vec![],
+        None => vec![Selector::default_asset()],
+    };
 
-    if (migration) {
-        clean_exit(ExitCode::Error, Some("Migration mode not fully implemented."))
+    if let Some(name) = matches.value_of("describe") {
+        return match describe(name) {
+            Err(e) => clean_exit(ExitCode::Error, Some(e.as_str())),
+            _ => clean_exit(ExitCode::Normal, None),
+        };
     }
 
-    // TODO: Fully implement apply_to logic.
-    let apply_to = vec![PacketApplied::Asset(0)];
+    if let Some(paths) = matches.values_of("diff") {
+        let paths: Vec<&Path> = paths.map(Path::new).collect();
+        if paths.iter().all(|p| is_stdio(p)) {
+            return clean_exit(ExitCode::Error, Some("Only one file of --diff can be read from stdin."));
+        }
+        let object_match = match matches.value_of("match_by") {
+            Some("name") => ObjectMatch::Name,
+            _ => ObjectMatch::Index,
+        };
+        let is_json = matches.value_of("diff_format") == Some("json");
+
+        return match diff_files(paths[0], paths[1], object_match, is_json, is_legacy, verbose) {
+            Err(e) => exit_on_error(e),
+            Ok(false) if !is_json => clean_exit(ExitCode::Normal, Some("No differences.")),
+            Ok(false) => clean_exit(ExitCode::Normal, None),
+            Ok(true) => clean_exit(ExitCode::Changed, None),
+        };
+    }
 
     // Read input file path.
     let input_path = Path::new(matches.value_of("input").unwrap());
@@ -555,19 +1575,84 @@ fn main() {
         clean_exit(ExitCode::Error, Some(message.as_str()))
     }
 
-    let input_type = match (
-        validate_file_as_extension(input_path, "gltf"),
-        validate_file_as_extension(input_path, "glb"),
-    ) {
-        (true, _) => InputType::Gltf,
-        (_, true) => InputType::Glb,
-        (_, _) => {
-            return clean_exit(
-                ExitCode::Error,
-                Some("Input file must be either a gltf or glb!"),
+    // stdin can only be read once.
+    let stdin_readers: Vec<&str> = ["input", "json", "xmp", "import_xliff"]
+        .iter()
+        .filter(|a| matches.value_of(a).is_some_and(|p| is_stdio(Path::new(p))))
+        .cloned()
+        .collect();
+    if stdin_readers.len() > 1 {
+        let message = format!("--{} cannot all read from stdin.", stdin_readers.join(" and --").replace('_', "-"));
+        return clean_exit(ExitCode::Error, Some(message.as_str()));
+    }
+
+    let input_type = match detect_input_type(input_path, matches.value_of("input_format"), verbose) {
+        Ok(t) => t,
+        Err(e) => return clean_exit(ExitCode::Error, Some(e.to_string().as_str())),
+    };
+
+    if let Some(json_path) = matches.value_of("extract") {
+        let json_path = Path::new(json_path);
+        if json_path.exists() && !is_stdio(json_path) && !matches.is_present("allow_overwrite") {
+            let message = format!(
+                "The JSON output path provided {}, already exists. Use the --allow-overwrite flag to allow overwriting.",
+                json_path.display()
+            );
+            return clean_exit(ExitCode::Error, Some(message.as_str()));
+        }
+
+        return match extract_json(input_path, &input_type, json_path, is_legacy, verbose) {
+            Err(e) => exit_on_error(e),
+            _ => clean_exit(ExitCode::Normal, None),
+        };
+    }
+
+    if let Some(xliff_path) = matches.value_of("export_xliff") {
+        let xliff_path = Path::new(xliff_path);
+        if xliff_path.exists() && !is_stdio(xliff_path) && !matches.is_present("allow_overwrite") {
+            let message = format!(
+                "The XLIFF output path provided {}, already exists. Use the --allow-overwrite flag to allow overwriting.",
+                xliff_path.display()
             );
+            return clean_exit(ExitCode::Error, Some(message.as_str()));
         }
-    };
+
+        let source_language = matches.value_of("source_lang").unwrap_or(DEFAULT_LANGUAGE);
+        let target_language = matches.value_of("target_lang");
+        return match export_xliff(input_path, &input_type, xliff_path, source_language, target_language, is_legacy, verbose) {
+            Err(e) => exit_on_error(e),
+            _ => clean_exit(ExitCode::Normal, None),
+        };
+    }
+
+    if let Some(xmp_path) = matches.value_of("extract_xmp") {
+        let padding = match matches.value_of("padding").unwrap_or("0").parse::<usize>() {
+            Ok(p) => p,
+            Err(_) => return clean_exit(ExitCode::Error, Some("--padding must be a number of bytes.")),
+        };
+        let options = ExtractXmpOptions {
+            is_legacy,
+            split: matches.is_present("split_packets"),
+            padding,
+            allow_overwrite: matches.is_present("allow_overwrite"),
+        };
+
+        return match extract_xmp(input_path, &input_type, Path::new(xmp_path), &options, verbose) {
+            Err(e) => exit_on_error(e),
+            _ => clean_exit(ExitCode::Normal, None),
+        };
+    }
+
+    if matches.is_present("validate") {
+        return match validate(input_path, &input_type, packet_options.require_default_language, is_legacy, verbose) {
+            Err(e) => exit_on_error(e),
+            Ok(0) => clean_exit(ExitCode::Normal, Some("No problems found.")),
+            Ok(count) => {
+                let message = format!("Found {} problem(s).", count);
+                clean_exit(ExitCode::Error, Some(message.as_str()))
+            }
+        };
+    }
 
     if matches.is_present("list") {
         let result = match input_type {
@@ -581,10 +1666,18 @@ fn main() {
         };
     }
 
-    let output_path = Path::new(matches.value_of("output").unwrap());
+    let in_place = matches.is_present("in_place");
+    if in_place && is_stdio(input_path) {
+        return clean_exit(ExitCode::Error, Some("--in-place cannot be used when reading from stdin."));
+    }
+    let is_dry_run = matches.is_present("dry_run");
+    let output_path = match matches.value_of("output") {
+        Some(output) => Path::new(output),
+        None => input_path,
+    };
 
     // TODO: Add a flag to allow overwriting files.
-    if output_path.exists() && !matches.is_present("allow_overwrite") {
+    if !in_place && !is_dry_run && output_path.exists() && !is_stdio(output_path) && !matches.is_present("allow_overwrite") {
         let message = format!(
             "The output path provided {}, already exists. This tool does not overwrite files by default. Use the --allow-overwrite flag to allow overwriting.",
             output_path
@@ -596,79 +1689,147 @@ fn main() {
         clean_exit(ExitCode::Error, Some(message.as_str()))
     }
 
-    let mode = match (matches.is_present("json"), matches.is_present("xmp")) {
-        (true, _) => {
-            MetadataInputMode::JSON(matches.value_of("json").unwrap_or_default().to_owned())
+    // The output is written in the format of the input, so an output extension must agree with it.
+    if let Some(hint) = InputType::from_extension(output_path).filter(|h| !in_place && *h != input_type) {
+        let message = format!(
+            "The output path {} names a {} file, but the input is {}. Converting between glTF and GLB is not supported.",
+            output_path.display(),
+            hint.name(),
+            input_type.name()
+        );
+        clean_exit(ExitCode::Error, Some(message.as_str()))
+    }
+
+    // With --in-place, the output is written to a temporary file that replaces the input.
+    let write_output = |output_path: &Path| -> Result<(), Box<dyn Error>> {
+        if migration {
+            return migrate(input_path, output_path, &input_type, verbose);
         }
-        (_, true) => MetadataInputMode::XMP(matches.value_of("xmp").unwrap_or_default().to_owned()),
-        (_, _) => {
-            // We need to fatally die here. Something is probably wrong with the Clap config.
-            panic!("FATAL: No mode set, but validation already performed. Check Clap config!")
+
+        if downgrading {
+            return downgrade(input_path, output_path, &input_type, verbose);
         }
-    };
-    match mode {
-        MetadataInputMode::JSON(p) => {
-            // TODO: Need to move this to the managers.
-            if is_legacy {
-                // KHR_xmp
-                let metadata_path = Path::new(p.as_str());
-                let metadata = match open_reader(metadata_path) {
-                    Ok(file) => read_legacy_json(file),
-                    Err(e) => Err(e),
-                };
-                match metadata {
-                    Ok(m) => match input_type {
-                        InputType::Gltf => {
-                            match update_gltf_legacy(input_path, output_path, &m, apply_to, verbose) {
-                                Err(e) => return exit_on_error(e),
-                                _ => (),
-                            }
-                        }
-                        InputType::Glb => {
-                            match update_glb_legacy(input_path, output_path, &m, apply_to, verbose) {
-                                Err(e) => return exit_on_error(e),
-                                _ => (),
-                            }
-                        }
-                    },
-                    Err(e) => return exit_on_error(e),
+
+        if let Some(packet) = matches.value_of("remove_packet") {
+            let parse_index = |value: &str| value.parse::<usize>().map_err(|_| format!("\"{}\" is not a valid packet index.", value));
+            let indices = parse_index(packet).and_then(|p| {
+                matches.value_of("reassign_to").map(parse_index).transpose().map(|m| (p, m))
+            });
+            let (packet, reassign_to) = indices?;
+
+            return remove(input_path, output_path, &input_type, packet, reassign_to, is_legacy, verbose);
+        }
+
+        let language_edits = LanguageEdits {
+            set: matches
+                .values_of("set_lang")
+                .map(|v| v.map(String::from).collect::<Vec<String>>())
+                .unwrap_or_default()
+                .chunks(3)
+                .map(|c| (c[0].clone(), c[1].clone(), c[2].clone()))
+                .collect(),
+            remove: pairs(matches.values_of("remove_lang")),
+            default: pairs(matches.values_of("default_lang")),
+        };
+        if !language_edits.set.is_empty() || !language_edits.remove.is_empty() || !language_edits.default.is_empty() {
+            let choice = match (matches.value_of("packet"), matches.value_of("packet_of")) {
+                (Some(index), _) => match index.parse::<usize>() {
+                    Ok(i) => PacketChoice::Index(i),
+                    Err(_) => return Err(format!("\"{}\" is not a valid packet index.", index).into()),
+                },
+                (_, objects) => PacketChoice::Objects(parse_object_selector(objects.unwrap_or("asset"))?),
+            };
+
+            return edit_languages(input_path, output_path, &input_type, &language_edits, &choice, is_legacy, verbose);
+        }
+
+        if let Some(xliff_path) = matches.value_of("import_xliff") {
+            return import_xliff(input_path, output_path, &input_type, Path::new(xliff_path), is_legacy, verbose);
+        }
+
+        if matches.is_present("gc") {
+            return garbage_collect(input_path, output_path, &input_type, is_legacy, verbose);
+        }
+
+        let is_rewriting = packet_options.normalize || packet_options.canonicalize_languages;
+        if is_rewriting && !matches.is_present("json") && !matches.is_present("xmp") {
+            return normalize(input_path, output_path, &input_type, &packet_options, is_legacy, verbose);
+        }
+
+        let mode = match (matches.is_present("json"), matches.is_present("xmp")) {
+            (true, _) => {
+                MetadataInputMode::Json(matches.value_of("json").unwrap_or_default().to_owned())
+            }
+            (_, true) => MetadataInputMode::Xmp(matches.value_of("xmp").unwrap_or_default().to_owned()),
+            (_, _) => {
+                // We need to fatally die here. Something is probably wrong with the Clap config.
+                panic!("FATAL: No mode set, but validation already performed. Check Clap config!")
+            }
+        };
+        match mode {
+            MetadataInputMode::Json(p) => {
+                // TODO: Need to move this to the managers.
+                if is_legacy {
+                    // KHR_xmp
+                    let metadata_path = Path::new(p.as_str());
+                    let metadata = match open_reader(metadata_path) {
+                        Ok(file) => read_legacy_json(file),
+                        Err(e) => Err(e),
+                    };
+                    metadata.and_then(|m| match input_type {
+                        InputType::Gltf => update_gltf_legacy(input_path, output_path, &m, &selectors, update_mode, &packet_options, verbose),
+                        InputType::Glb => update_glb_legacy(input_path, output_path, &m, &selectors, update_mode, &packet_options, verbose),
+                    })
+                } else {
+                    // KHR_xmp_json_ld
+                    let metadata_path = Path::new(p.as_str());
+                    let metadata = match open_reader(metadata_path) {
+                        Ok(file) => read_json(file),
+                        Err(e) => Err(e),
+                    };
+                    metadata.and_then(|m| match input_type {
+                        InputType::Gltf => update_gltf(input_path, output_path, &m, &selectors, update_mode, &packet_options, verbose),
+                        InputType::Glb => update_glb(input_path, output_path, &m, &selectors, update_mode, &packet_options, verbose),
+                    })
                 }
-            } else {
-                // KHR_xmp_json_ld
+            }
+            MetadataInputMode::Xmp(p) => {
+                // XMP input always produces KHR_xmp_json_ld packets.
                 let metadata_path = Path::new(p.as_str());
                 let metadata = match open_reader(metadata_path) {
-                    Ok(file) => read_json(file),
+                    Ok(file) => read_xmp(file),
                     Err(e) => Err(e),
                 };
-                match metadata {
-                    Ok(m) => match input_type {
-                        InputType::Gltf => {
-                            match update_gltf(input_path, output_path, &m, apply_to, verbose) {
-                                Err(e) => return exit_on_error(e),
-                                _ => (),
-                            }
-                        }
-                        InputType::Glb => {
-                            match update_glb(input_path, output_path, &m, apply_to, verbose) {
-                                Err(e) => return exit_on_error(e),
-                                _ => (),
-                            }
-                        }
-                    },
-                    Err(e) => return exit_on_error(e),
-                }
-            };
-        }
-        MetadataInputMode::XMP(_path) => {
-            // TODO: Add XMP file input support.
-            return clean_exit(ExitCode::Error, Some("XMP input is not yet implemented."));
-        }
-        MetadataInputMode::Manual => {
-            // TODO: Add manual input support.
-            return clean_exit(
-                ExitCode::Error,
-                Some("Manual input is not yet implemented."),
-            );
+                metadata.and_then(|m| match input_type {
+                    InputType::Gltf => update_gltf(input_path, output_path, &m, &selectors, update_mode, &packet_options, verbose),
+                    InputType::Glb => update_glb(input_path, output_path, &m, &selectors, update_mode, &packet_options, verbose),
+                })
+            }
+            MetadataInputMode::Manual => {
+                // TODO: Add manual input support.
+                Err("Manual input is not yet implemented.".into())
+            }
         }
+    };
+
+    if is_dry_run {
+        return match dry_run(input_path, &input_type, is_legacy, verbose, write_output) {
+            Err(e) => exit_on_error(e),
+            Ok(false) => clean_exit(ExitCode::Normal, Some("No changes.")),
+            Ok(true) => clean_exit(ExitCode::Changed, None),
+        };
+    }
+
+    let result = if in_place {
+        let backup_suffix = matches
+            .is_present("backup")
+            .then(|| matches.value_of("backup").unwrap_or(DEFAULT_BACKUP_SUFFIX));
+        replace_file(input_path, backup_suffix, write_output)
+    } else {
+        write_output(output_path)
+    };
+    match result {
+        Err(e) => exit_on_error(e),
+        _ => clean_exit(ExitCode::Normal, None),
     }
 }
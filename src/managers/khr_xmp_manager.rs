@@ -1,15 +1,19 @@
 use crate::json_models::gltf::Gltf;
-use crate::{PacketApplied, NO_METADATA_FOUND_ERROR};
-use crate::json_models::extension::{PacketExtension, ExtensionsOnly};
-use crate::managers::Manager;
+use crate::managers::{packet_extension_mut, packet_extensions, packet_extensions_mut, Manager, PacketTarget};
+use crate::NO_METADATA_FOUND_ERROR;
+use crate::json_models::extension::PacketExtension;
 use crate::json_models::khr_xmp::KhrXmpPacket;
+use crate::managers::migration::inline_context;
+use crate::xmp::namespaces::{complete_context, used_prefixes, ContextReport};
+use serde_json::Value;
+use std::collections::BTreeSet;
 
 pub struct KhrXmpManager {
     gltf: Gltf,
 }
 
 impl Manager for KhrXmpManager {
-    fn new(g: Gltf) -> KhrXmpManager {
+    fn new(g: Gltf) -> Self {
         KhrXmpManager { gltf: g }
     }
 
@@ -17,76 +21,29 @@ impl Manager for KhrXmpManager {
         &self.gltf
     }
 
+    fn into_gltf(self) -> Gltf {
+        self.gltf
+    }
+
     fn print_gltf(&self) -> Result<(), String> {
         match &self.gltf.extensions {
             Some(extension) => match &extension.khr_xmp {
                 Some(xmp) => {
-                    let mut applied_packets: Vec<PacketApplied> = vec![];
-                    let empty_vec: Vec<ExtensionsOnly> = Vec::new();
-
-                    match get_packet_value(&self.gltf.asset.extensions) {
-                        Some(index) => applied_packets.push(PacketApplied::Asset(index)),
-                        None => (),
-                    }
-
-                    // TODO: Need to clean this up. Lots of repeat code here.
-                    for animation in self.gltf.animations.as_ref().unwrap_or(&empty_vec) {
-                        match get_packet_value(&animation.extensions) {
-                            Some(index) => applied_packets.push(PacketApplied::Animations(index)),
-                            None => (),
-                        }
-                    }
-
-                    for image in self.gltf.images.as_ref().unwrap_or(&empty_vec) {
-                        match get_packet_value(&image.extensions) {
-                            Some(index) => applied_packets.push(PacketApplied::Images(index)),
-                            None => (),
-                        }
-                    }
-
-                    for material in self.gltf.materials.as_ref().unwrap_or(&empty_vec) {
-                        match get_packet_value(&material.extensions) {
-                            Some(index) => applied_packets.push(PacketApplied::Materials(index)),
-                            None => (),
-                        }
-                    }
-
-                    for mesh in self.gltf.meshes.as_ref().unwrap_or(&empty_vec) {
-                        match get_packet_value(&mesh.extensions) {
-                            Some(index) => applied_packets.push(PacketApplied::Meshes(index)),
-                            None => (),
-                        }
-                    }
-
-                    for node in self.gltf.nodes.as_ref().unwrap_or(&empty_vec) {
-                        match get_packet_value(&node.extensions) {
-                            Some(index) => applied_packets.push(PacketApplied::Nodes(index)),
-                            None => (),
-                        }
-                    }
-
-                    for scene in self.gltf.scenes.as_ref().unwrap_or(&empty_vec) {
-                        match get_packet_value(&scene.extensions) {
-                            Some(index) => applied_packets.push(PacketApplied::Scenes(index)),
-                            None => (),
-                        }
-                    }
-
                     println!("KHR_xmp extension value:");
                     println!("{}", serde_json::to_string_pretty(&xmp).unwrap());
 
                     // TODO: I want to also include the name, if available, to make it easier to
                     //   figure out which packet corresponds exactly to which image/mesh/etc.
                     println!("\nPackets applied at:");
-                    for packet in applied_packets {
-                        match packet {
-                            PacketApplied::Asset(index) => println!("\tAssets: {}", index),
-                            PacketApplied::Animations(index) => println!("\tAnimations: {}", index),
-                            PacketApplied::Images(index) => println!("\tImages: {}", index),
-                            PacketApplied::Materials(index) => println!("\tMaterials: {}", index),
-                            PacketApplied::Meshes(index) => println!("\tMeshes: {}", index),
-                            PacketApplied::Nodes(index) => println!("\tNodes: {}", index),
-                            PacketApplied::Scenes(index) => println!("\tScenes: {}", index),
+                    for (target, index) in self.get_packet_references() {
+                        match target {
+                            PacketTarget::Asset => println!("\tAssets: {}", index),
+                            PacketTarget::Animations(_) => println!("\tAnimations: {}", index),
+                            PacketTarget::Images(_) => println!("\tImages: {}", index),
+                            PacketTarget::Materials(_) => println!("\tMaterials: {}", index),
+                            PacketTarget::Meshes(_) => println!("\tMeshes: {}", index),
+                            PacketTarget::Nodes(_) => println!("\tNodes: {}", index),
+                            PacketTarget::Scenes(_) => println!("\tScenes: {}", index),
                         }
                     }
 
@@ -98,169 +55,88 @@ impl Manager for KhrXmpManager {
         }
     }
 
-    fn clear_applied_packets(&mut self) {
-        // There is maybe a more elegant way to do this, but brute force it for now.
-        if let Some(mut e) = self.gltf.asset.extensions.as_mut() {
-            e.khr_xmp = None;
-        }
+    fn get_packets(&self) -> Result<Vec<Value>, String> {
+        let xmp = self
+            .gltf
+            .extensions
+            .as_ref()
+            .and_then(|e| e.khr_xmp.as_ref())
+            .ok_or_else(|| NO_METADATA_FOUND_ERROR.to_string())?;
+
+        xmp.packets
+            .iter()
+            .enumerate()
+            .map(|(index, packet)| inline_context(&xmp.context, packet.clone(), index))
+            .collect()
+    }
 
-        if *&self.gltf.animations.is_some() {
-            for animation in self.gltf.animations.as_mut().unwrap() {
-                if let Some(mut e) = animation.extensions.as_mut() {
-                    e.khr_xmp = None;
-                }
-            }
-        }
+    fn packets_mut(&mut self) -> Result<&mut Vec<Value>, String> {
+        self.gltf
+            .extensions
+            .as_mut()
+            .and_then(|e| e.khr_xmp.as_mut())
+            .map(|xmp| &mut xmp.packets)
+            .ok_or_else(|| NO_METADATA_FOUND_ERROR.to_string())
+    }
 
-        if *&self.gltf.images.is_some() {
-            for image in self.gltf.images.as_mut().unwrap() {
-                if let Some(mut e) = image.extensions.as_mut() {
-                    e.khr_xmp = None;
-                }
+    fn clear_applied_packets(&mut self) {
+        for (_, extension) in packet_extensions_mut(&mut self.gltf) {
+            if let Some(e) = extension.as_mut() {
+                e.khr_xmp = None;
             }
         }
+    }
 
-        if *&self.gltf.materials.is_some() {
-            for material in self.gltf.materials.as_mut().unwrap() {
-                if let Some(mut e) = material.extensions.as_mut() {
-                    e.khr_xmp = None;
-                }
-            }
+    fn clear_packet_reference(&mut self, target: &PacketTarget) -> Result<(), String> {
+        if let Some(e) = packet_extension_mut(&mut self.gltf, target)?.as_mut() {
+            e.khr_xmp = None;
         }
 
-        if *&self.gltf.meshes.is_some() {
-            for mesh in self.gltf.meshes.as_mut().unwrap() {
-                if let Some(mut e) = mesh.extensions.as_mut() {
-                    e.khr_xmp = None;
-                }
-            }
-        }
+        Ok(())
+    }
 
-        if *&self.gltf.nodes.is_some() {
-            for node in self.gltf.nodes.as_mut().unwrap() {
-                if let Some(mut e) = node.extensions.as_mut() {
-                    e.khr_xmp = None;
-                }
-            }
-        }
+    fn complete_contexts(&mut self) -> Result<Vec<(String, ContextReport)>, String> {
+        let xmp = self
+            .gltf
+            .extensions
+            .as_mut()
+            .and_then(|e| e.khr_xmp.as_mut())
+            .ok_or_else(|| NO_METADATA_FOUND_ERROR.to_string())?;
+
+        // All packets share the global @context.
+        let prefixes: BTreeSet<String> = xmp.packets.iter().flat_map(used_prefixes).collect();
+        Ok(match xmp.context.as_object_mut() {
+            Some(context) => vec![("the KHR_xmp @context".to_string(), complete_context(context, &prefixes))],
+            None => vec![],
+        })
+    }
 
-        if *&self.gltf.scenes.is_some() {
-            for scene in self.gltf.scenes.as_mut().unwrap() {
-                if let Some(mut e) = scene.extensions.as_mut() {
-                    e.khr_xmp = None;
-                }
-            }
-        }
+    fn get_packet_references(&self) -> Vec<(PacketTarget, u64)> {
+        packet_extensions(&self.gltf)
+            .into_iter()
+            .filter_map(|(target, extension)| get_packet_value(extension).map(|index| (target, index)))
+            .collect()
     }
 
-    //noinspection DuplicatedCode There's a lot of repeat code here because dealing with the lifetimes of the serde object is difficult due to our use of flatten.
-    fn set_applied_packets(&mut self, apply_to: Vec<PacketApplied>) {
-        // Right now we just apply to _all_ of a category.
-        for packet in apply_to {
-            match packet {
-                PacketApplied::Asset(i) => {
-                    if let Some(e) = self.gltf.asset.extensions.as_mut() {
-                        e.khr_xmp = Some(KhrXmpPacket { packet: Some(i) });
-                    } else {
-                        self.gltf.asset.extensions = Some(PacketExtension {
-                            khr_xmp: Some(KhrXmpPacket { packet: Some(i) }),
-                            khr_xmp_json_ld: None,
-                            other_extensions: Default::default(),
-                        });
-                    };
-                }
-                PacketApplied::Animations(i) => {
-                    for animation in self.gltf.animations.as_mut().unwrap() {
-                        if let Some(e) = animation.extensions.as_mut() {
-                            e.khr_xmp = Some(KhrXmpPacket { packet: Some(i) });
-                        } else {
-                            animation.extensions = Some(PacketExtension {
-                                khr_xmp: Some(KhrXmpPacket { packet: Some(i) }),
-                                khr_xmp_json_ld: None,
-                                other_extensions: Default::default(),
-                            });
-                        };
-                    }
-                }
-                PacketApplied::Images(i) => {
-                    for image in self.gltf.images.as_mut().unwrap() {
-                        if let Some(e) = image.extensions.as_mut() {
-                            e.khr_xmp = Some(KhrXmpPacket { packet: Some(i) });
-                        } else {
-                            image.extensions = Some(PacketExtension {
-                                khr_xmp: Some(KhrXmpPacket { packet: Some(i) }),
-                                khr_xmp_json_ld: None,
-                                other_extensions: Default::default(),
-                            });
-                        };
-                    }
-                }
-                PacketApplied::Materials(i) => {
-                    for material in self.gltf.materials.as_mut().unwrap() {
-                        if let Some(e) = material.extensions.as_mut() {
-                            e.khr_xmp = Some(KhrXmpPacket { packet: Some(i) });
-                        } else {
-                            material.extensions = Some(PacketExtension {
-                                khr_xmp: Some(KhrXmpPacket { packet: Some(i) }),
-                                khr_xmp_json_ld: None,
-                                other_extensions: Default::default(),
-                            });
-                        };
-                    }
-                }
-                PacketApplied::Meshes(i) => {
-                    for mesh in self.gltf.meshes.as_mut().unwrap() {
-                        if let Some(e) = mesh.extensions.as_mut() {
-                            e.khr_xmp = Some(KhrXmpPacket { packet: Some(i) });
-                        } else {
-                            mesh.extensions = Some(PacketExtension {
-                                khr_xmp: Some(KhrXmpPacket { packet: Some(i) }),
-                                khr_xmp_json_ld: None,
-                                other_extensions: Default::default(),
-                            });
-                        };
-                    }
-                }
-                PacketApplied::Nodes(i) => {
-                    for node in self.gltf.nodes.as_mut().unwrap() {
-                        if let Some(e) = node.extensions.as_mut() {
-                            e.khr_xmp = Some(KhrXmpPacket { packet: Some(i) });
-                        } else {
-                            node.extensions = Some(PacketExtension {
-                                khr_xmp: Some(KhrXmpPacket { packet: Some(i) }),
-                                khr_xmp_json_ld: None,
-                                other_extensions: Default::default(),
-                            });
-                        };
-                    }
-                }
-                PacketApplied::Scenes(i) => {
-                    for scene in self.gltf.scenes.as_mut().unwrap() {
-                        if let Some(e) = scene.extensions.as_mut() {
-                            e.khr_xmp = Some(KhrXmpPacket { packet: Some(i) });
-                        } else {
-                            scene.extensions = Some(PacketExtension {
-                                khr_xmp: Some(KhrXmpPacket { packet: Some(i) }),
-                                khr_xmp_json_ld: None,
-                                other_extensions: Default::default(),
-                            });
-                        };
-                    }
-                }
-            }
+    fn set_packet_reference(&mut self, target: &PacketTarget, packet: u64) -> Result<(), String> {
+        let extension = packet_extension_mut(&mut self.gltf, target)?;
+        if let Some(e) = extension.as_mut() {
+            e.khr_xmp = Some(KhrXmpPacket { packet: Some(packet) });
+        } else {
+            *extension = Some(PacketExtension {
+                khr_xmp: Some(KhrXmpPacket { packet: Some(packet) }),
+                khr_xmp_json_ld: None,
+                other_extensions: Default::default(),
+            });
         }
+
+        Ok(())
     }
 }
 
 fn get_packet_value(extension: &Option<PacketExtension>) -> Option<u64> {
-    match extension {
-        None => None,
-        Some(ex) => match &ex.khr_xmp {
-            None => None,
-            Some(xmp) => match xmp.packet {
-                None => None,
-                Some(index) => Some(index),
-            },
-        },
-    }
+    extension
+        .as_ref()
+        .and_then(|ex| ex.khr_xmp.as_ref())
+        .and_then(|xmp| xmp.packet)
 }
@@ -0,0 +1,361 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::json_models::gltf::Gltf;
+use crate::json_models::khr_xmp::{self, KhrXmp};
+use crate::json_models::khr_xmp_json_ld::{self, KhrXmpJsonLd};
+use crate::managers::khr_xmp_json_ld_manager::KhrXmpJsonLdManager;
+use crate::managers::khr_xmp_manager::KhrXmpManager;
+use crate::managers::Manager;
+use crate::NO_METADATA_FOUND_ERROR;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// A prefix bound to different IRIs by two `KHR_xmp_json_ld` packets.
+#[derive(Debug, PartialEq)]
+pub struct PrefixCollision {
+    pub prefix: String,
+    pub iri: String,
+    pub packet: usize,
+    pub conflicting_iri: String,
+}
+
+impl fmt::Display for PrefixCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Prefix \"{}\" is bound to <{}> globally, but packet {} binds it to <{}>. The packet keeps its own binding.",
+            self.prefix, self.iri, self.packet, self.conflicting_iri
+        )
+    }
+}
+
+/// Converts the `KHR_xmp` metadata of a glTF document into `KHR_xmp_json_ld` metadata.
+///
+/// The global `@context` is inlined into every packet, every per-object packet reference is
+/// rewritten, and `extensionsUsed` is updated to list the new extension in place of the old one.
+pub fn migrate_to_json_ld(mut gltf: Gltf) -> Result<Gltf, String> {
+    let legacy = match gltf.extensions.as_mut() {
+        Some(extensions) => {
+            if extensions.khr_xmp_json_ld.is_some() {
+                return Err(
+                    "The input file already contains KHR_xmp_json_ld metadata. Migration aborted."
+                        .to_string(),
+                );
+            }
+
+            match extensions.khr_xmp.take() {
+                Some(xmp) => xmp,
+                None => return Err(NO_METADATA_FOUND_ERROR.to_string()),
+            }
+        }
+        None => return Err(NO_METADATA_FOUND_ERROR.to_string()),
+    };
+
+    let mut packets = Vec::with_capacity(legacy.packets.len());
+    for (index, packet) in legacy.packets.into_iter().enumerate() {
+        packets.push(inline_context(&legacy.context, packet, index)?);
+    }
+
+    let mut legacy_manager = KhrXmpManager::new(gltf);
+    let references = legacy_manager.get_packet_references();
+    legacy_manager.clear_applied_packets();
+
+    let mut manager = KhrXmpJsonLdManager::new(legacy_manager.into_gltf());
+    for (target, packet) in references {
+        manager.set_packet_reference(&target, packet)?;
+    }
+
+    let mut gltf = manager.into_gltf();
+    if let Some(extensions) = gltf.extensions.as_mut() {
+        extensions.khr_xmp_json_ld = Some(KhrXmpJsonLd { packets });
+    }
+
+    replace_extension_used(&mut gltf, khr_xmp::EXTENSION_NAME, khr_xmp_json_ld::EXTENSION_NAME);
+
+    Ok(gltf)
+}
+
+/// Converts the `KHR_xmp_json_ld` metadata of a glTF document back into legacy `KHR_xmp` metadata.
+///
+/// The prefixes of every packet are merged into one top-level `@context`. When two packets bind the
+/// same prefix to different IRIs, the first binding wins and the later packet keeps its binding in a
+/// packet-level `@context`; each such case is returned as a `PrefixCollision`.
+pub fn downgrade_to_legacy(mut gltf: Gltf) -> Result<(Gltf, Vec<PrefixCollision>), String> {
+    let json_ld = match gltf.extensions.as_mut() {
+        Some(extensions) => {
+            if extensions.khr_xmp.is_some() {
+                return Err(
+                    "The input file already contains KHR_xmp metadata. Downgrade aborted."
+                        .to_string(),
+                );
+            }
+
+            match extensions.khr_xmp_json_ld.take() {
+                Some(xmp) => xmp,
+                None => return Err(NO_METADATA_FOUND_ERROR.to_string()),
+            }
+        }
+        None => return Err(NO_METADATA_FOUND_ERROR.to_string()),
+    };
+
+    let mut context = Map::new();
+    let mut collisions = Vec::new();
+    let mut packets = Vec::with_capacity(json_ld.packets.len());
+    for (index, packet) in json_ld.packets.into_iter().enumerate() {
+        packets.push(hoist_context(&mut context, &mut collisions, packet, index)?);
+    }
+
+    let mut manager = KhrXmpJsonLdManager::new(gltf);
+    let references = manager.get_packet_references();
+    manager.clear_applied_packets();
+
+    let mut legacy_manager = KhrXmpManager::new(manager.into_gltf());
+    for (target, packet) in references {
+        legacy_manager.set_packet_reference(&target, packet)?;
+    }
+
+    let mut gltf = legacy_manager.into_gltf();
+    if let Some(extensions) = gltf.extensions.as_mut() {
+        extensions.khr_xmp = Some(KhrXmp {
+            context: Value::Object(context),
+            packets,
+        });
+    }
+
+    replace_extension_used(&mut gltf, khr_xmp_json_ld::EXTENSION_NAME, khr_xmp::EXTENSION_NAME);
+
+    Ok((gltf, collisions))
+}
+
+/// Produces a standalone `KHR_xmp_json_ld` packet from a `KHR_xmp` packet and the global context.
+/// Prefixes declared by the packet itself take precedence over the global ones.
+pub fn inline_context(global_context: &Value, packet: Value, index: usize) -> Result<Value, String> {
+    let mut packet = match packet {
+        Value::Object(p) => p,
+        _ => return Err(format!("KHR_xmp packet {} is not a JSON object.", index)),
+    };
+
+    let mut context = match global_context {
+        Value::Object(c) => c.clone(),
+        Value::Null => Map::new(),
+        _ => return Err("The KHR_xmp @context must be a JSON object.".to_string()),
+    };
+
+    match packet.remove("@context") {
+        Some(Value::Object(local)) => context.extend(local),
+        Some(_) => return Err(format!("The @context of KHR_xmp packet {} must be a JSON object.", index)),
+        None => (),
+    }
+
+    packet.insert("@context".to_string(), Value::Object(context));
+
+    Ok(Value::Object(packet))
+}
+
+/// Moves the prefix declarations of a `KHR_xmp_json_ld` packet into the shared global context.
+/// Keywords such as `@language` and colliding prefixes stay in the packet's own `@context`.
+fn hoist_context(
+    global_context: &mut Map<String, Value>,
+    collisions: &mut Vec<PrefixCollision>,
+    packet: Value,
+    index: usize,
+) -> Result<Value, String> {
+    let mut packet = match packet {
+        Value::Object(p) => p,
+        _ => return Err(format!("KHR_xmp_json_ld packet {} is not a JSON object.", index)),
+    };
+
+    let local = match packet.remove("@context") {
+        Some(Value::Object(local)) => local,
+        Some(_) => return Err(format!("The @context of KHR_xmp_json_ld packet {} must be a JSON object.", index)),
+        None => Map::new(),
+    };
+
+    let mut remaining = Map::new();
+    for (prefix, iri) in local {
+        if prefix.starts_with('@') || !iri.is_string() {
+            remaining.insert(prefix, iri);
+            continue;
+        }
+
+        match global_context.get(&prefix) {
+            None => {
+                global_context.insert(prefix, iri);
+            }
+            Some(existing) if *existing == iri => (),
+            Some(existing) => {
+                collisions.push(PrefixCollision {
+                    prefix: prefix.clone(),
+                    iri: existing.as_str().unwrap_or_default().to_string(),
+                    packet: index,
+                    conflicting_iri: iri.as_str().unwrap_or_default().to_string(),
+                });
+                remaining.insert(prefix, iri);
+            }
+        }
+    }
+
+    if !remaining.is_empty() {
+        packet.insert("@context".to_string(), Value::Object(remaining));
+    }
+
+    Ok(Value::Object(packet))
+}
+
+/// Swaps one extension name for another in `extensionsUsed`. The old extension is also dropped
+/// from `extensionsRequired`; neither metadata extension should ever be required.
+fn replace_extension_used(gltf: &mut Gltf, old: &str, new: &str) {
+    match gltf.extensions_used.as_mut() {
+        Some(extensions_used) => {
+            extensions_used.retain(|e| e != old);
+            if !extensions_used.iter().any(|e| e == new) {
+                extensions_used.push(new.to_string());
+            }
+        }
+        None => gltf.extensions_used = Some(vec![new.to_string()]),
+    }
+
+    if let Some(Value::Array(required)) = gltf.other_fields.get_mut("extensionsRequired") {
+        required.retain(|e| e.as_str() != Some(old));
+        if required.is_empty() {
+            gltf.other_fields.remove("extensionsRequired");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::managers::PacketTarget;
+    use serde_json::json;
+
+    fn legacy_gltf() -> Gltf {
+        serde_json::from_value(json!({
+            "asset": {
+                "version": "2.0",
+                "extensions": { "KHR_xmp": { "packet": 0 } }
+            },
+            "extensionsUsed": ["KHR_xmp"],
+            "extensions": {
+                "KHR_xmp": {
+                    "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+                    "packets": [
+                        { "dc:title": "Asset" },
+                        {
+                            "@context": { "xmp": "http://ns.adobe.com/xap/1.0/" },
+                            "xmp:Label": "Node"
+                        }
+                    ]
+                }
+            },
+            "nodes": [
+                { "name": "First" },
+                { "name": "Second", "extensions": { "KHR_xmp": { "packet": 1 } } }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn migrates_packets_and_references() {
+        let gltf = migrate_to_json_ld(legacy_gltf()).unwrap();
+
+        let extensions = gltf.extensions.as_ref().unwrap();
+        assert!(extensions.khr_xmp.is_none());
+
+        let packets = &extensions.khr_xmp_json_ld.as_ref().unwrap().packets;
+        assert_eq!(
+            packets[0],
+            json!({
+                "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+                "dc:title": "Asset"
+            })
+        );
+        assert_eq!(
+            packets[1]["@context"],
+            json!({
+                "dc": "http://purl.org/dc/elements/1.1/",
+                "xmp": "http://ns.adobe.com/xap/1.0/"
+            })
+        );
+
+        let manager = KhrXmpJsonLdManager::new(gltf);
+        assert_eq!(
+            manager.get_packet_references(),
+            vec![(PacketTarget::Asset, 0), (PacketTarget::Nodes(1), 1)]
+        );
+        assert!(KhrXmpManager::new(manager.into_gltf())
+            .get_packet_references()
+            .is_empty());
+    }
+
+    #[test]
+    fn replaces_extensions_used() {
+        let gltf = migrate_to_json_ld(legacy_gltf()).unwrap();
+        assert_eq!(gltf.extensions_used, Some(vec!["KHR_xmp_json_ld".to_string()]));
+    }
+
+    #[test]
+    fn downgrade_reverses_migration() {
+        let migrated = migrate_to_json_ld(legacy_gltf()).unwrap();
+        let (gltf, collisions) = downgrade_to_legacy(migrated).unwrap();
+        assert!(collisions.is_empty());
+
+        let expected = serde_json::to_value(legacy_gltf()).unwrap();
+        let mut actual = serde_json::to_value(gltf).unwrap();
+
+        // The node-level prefix is hoisted into the global context.
+        actual["extensions"]["KHR_xmp"]["@context"]
+            .as_object_mut()
+            .unwrap()
+            .remove("xmp");
+        actual["extensions"]["KHR_xmp"]["packets"][1]["@context"] =
+            json!({ "xmp": "http://ns.adobe.com/xap/1.0/" });
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn downgrade_reports_prefix_collisions() {
+        let gltf: Gltf = serde_json::from_value(json!({
+            "asset": { "version": "2.0" },
+            "extensionsUsed": ["KHR_xmp_json_ld"],
+            "extensions": {
+                "KHR_xmp_json_ld": {
+                    "packets": [
+                        { "@context": { "ex": "http://example.com/a#" }, "ex:a": "1" },
+                        { "@context": { "ex": "http://example.com/b#", "@language": "en" }, "ex:b": "2" }
+                    ]
+                }
+            }
+        }))
+        .unwrap();
+
+        let (gltf, collisions) = downgrade_to_legacy(gltf).unwrap();
+        assert_eq!(
+            collisions,
+            vec![PrefixCollision {
+                prefix: "ex".to_string(),
+                iri: "http://example.com/a#".to_string(),
+                packet: 1,
+                conflicting_iri: "http://example.com/b#".to_string(),
+            }]
+        );
+
+        let xmp = gltf.extensions.unwrap().khr_xmp.unwrap();
+        assert_eq!(xmp.context, json!({ "ex": "http://example.com/a#" }));
+        assert_eq!(
+            xmp.packets[1]["@context"],
+            json!({ "ex": "http://example.com/b#", "@language": "en" })
+        );
+        assert_eq!(gltf.extensions_used, Some(vec!["KHR_xmp".to_string()]));
+    }
+
+    #[test]
+    fn fails_without_legacy_metadata() {
+        let gltf: Gltf = serde_json::from_value(json!({ "asset": { "version": "2.0" } })).unwrap();
+        assert!(migrate_to_json_ld(gltf).is_err());
+    }
+}
@@ -2,20 +2,160 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::json_models::extension::{ExtensionsOnly, PacketExtension};
 use crate::json_models::gltf::Gltf;
+use crate::xmp::namespaces::ContextReport;
 use crate::PacketApplied;
+use serde_json::Value;
+use std::fmt;
 
 pub mod khr_xmp_manager;
 pub mod khr_xmp_json_ld_manager;
+pub mod migration;
+pub mod packets;
+pub mod update;
+
+/// An object within the glTF document that may reference a metadata packet.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PacketTarget {
+    Asset,
+    Animations(usize),
+    Images(usize),
+    Materials(usize),
+    Meshes(usize),
+    Nodes(usize),
+    Scenes(usize),
+}
+
+impl fmt::Display for PacketTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketTarget::Asset => write!(f, "asset"),
+            PacketTarget::Animations(i) => write!(f, "animations[{}]", i),
+            PacketTarget::Images(i) => write!(f, "images[{}]", i),
+            PacketTarget::Materials(i) => write!(f, "materials[{}]", i),
+            PacketTarget::Meshes(i) => write!(f, "meshes[{}]", i),
+            PacketTarget::Nodes(i) => write!(f, "nodes[{}]", i),
+            PacketTarget::Scenes(i) => write!(f, "scenes[{}]", i),
+        }
+    }
+}
 
 pub trait Manager {
     fn new(g: Gltf) -> Self where Self: Sized;
 
     fn get_gltf(&self) -> &Gltf;
 
+    fn into_gltf(self) -> Gltf where Self: Sized;
+
     fn print_gltf(&self) -> Result<(), String>;
 
+    /// Returns each packet as a standalone JSON-LD object carrying its full `@context`.
+    fn get_packets(&self) -> Result<Vec<Value>, String>;
+
+    /// Returns the packets as stored in the extension, for editing in place.
+    fn packets_mut(&mut self) -> Result<&mut Vec<Value>, String>;
+
     fn clear_applied_packets(&mut self);
 
-    fn set_applied_packets(&mut self, apply_to: Vec<PacketApplied>);
+    /// Removes the packet reference of a single object, if it has one.
+    fn clear_packet_reference(&mut self, target: &PacketTarget) -> Result<(), String>;
+
+    /// Declares the standard XMP prefixes that packets use but do not declare. Returns a report
+    /// for each `@context` that was examined, labelled for messages.
+    fn complete_contexts(&mut self) -> Result<Vec<(String, ContextReport)>, String>;
+
+    /// Returns every packet reference in the document along with the object holding it.
+    fn get_packet_references(&self) -> Vec<(PacketTarget, u64)>;
+
+    /// Points a single object at the given packet index.
+    fn set_packet_reference(&mut self, target: &PacketTarget, packet: u64) -> Result<(), String>;
+
+    /// Applies each packet to its target object, failing if a packet index does not exist.
+    fn set_applied_packets(&mut self, apply_to: Vec<PacketApplied>) -> Result<(), String> {
+        let packet_count = self.get_packets()?.len();
+
+        for applied in apply_to {
+            if applied.packet as usize >= packet_count {
+                return Err(format!(
+                    "Packet {} cannot be applied to {}. The metadata only contains {} packet(s).",
+                    applied.packet, applied.target, packet_count
+                ));
+            }
+            self.set_packet_reference(&applied.target, applied.packet)?;
+        }
+
+        Ok(())
+    }
+}
+
+type TargetConstructor = fn(usize) -> PacketTarget;
+
+/// Returns the extensions of the asset and of every object type that may reference a packet.
+pub fn packet_extensions(gltf: &Gltf) -> Vec<(PacketTarget, &Option<PacketExtension>)> {
+    let mut result = vec![(PacketTarget::Asset, &gltf.asset.extensions)];
+
+    let categories: Vec<(&Option<Vec<ExtensionsOnly>>, TargetConstructor)> = vec![
+        (&gltf.animations, PacketTarget::Animations),
+        (&gltf.images, PacketTarget::Images),
+        (&gltf.materials, PacketTarget::Materials),
+        (&gltf.meshes, PacketTarget::Meshes),
+        (&gltf.nodes, PacketTarget::Nodes),
+        (&gltf.scenes, PacketTarget::Scenes),
+    ];
+
+    for (objects, target) in categories {
+        if let Some(objects) = objects {
+            for (i, object) in objects.iter().enumerate() {
+                result.push((target(i), &object.extensions));
+            }
+        }
+    }
+
+    result
+}
+
+/// Mutable counterpart of `packet_extensions`.
+pub fn packet_extensions_mut(gltf: &mut Gltf) -> Vec<(PacketTarget, &mut Option<PacketExtension>)> {
+    let mut result = vec![(PacketTarget::Asset, &mut gltf.asset.extensions)];
+
+    let categories: Vec<(&mut Option<Vec<ExtensionsOnly>>, TargetConstructor)> = vec![
+        (&mut gltf.animations, PacketTarget::Animations),
+        (&mut gltf.images, PacketTarget::Images),
+        (&mut gltf.materials, PacketTarget::Materials),
+        (&mut gltf.meshes, PacketTarget::Meshes),
+        (&mut gltf.nodes, PacketTarget::Nodes),
+        (&mut gltf.scenes, PacketTarget::Scenes),
+    ];
+
+    for (objects, target) in categories {
+        if let Some(objects) = objects {
+            for (i, object) in objects.iter_mut().enumerate() {
+                result.push((target(i), &mut object.extensions));
+            }
+        }
+    }
+
+    result
+}
+
+/// Looks up the extensions of a single object, failing if the object does not exist.
+pub fn packet_extension_mut<'a>(
+    gltf: &'a mut Gltf,
+    target: &PacketTarget,
+) -> Result<&'a mut Option<PacketExtension>, String> {
+    let (objects, index) = match target {
+        PacketTarget::Asset => return Ok(&mut gltf.asset.extensions),
+        PacketTarget::Animations(i) => (gltf.animations.as_mut(), *i),
+        PacketTarget::Images(i) => (gltf.images.as_mut(), *i),
+        PacketTarget::Materials(i) => (gltf.materials.as_mut(), *i),
+        PacketTarget::Meshes(i) => (gltf.meshes.as_mut(), *i),
+        PacketTarget::Nodes(i) => (gltf.nodes.as_mut(), *i),
+        PacketTarget::Scenes(i) => (gltf.scenes.as_mut(), *i),
+    };
+
+    match objects.and_then(|o| o.get_mut(index)) {
+        Some(object) => Ok(&mut object.extensions),
+        None => Err(format!("{} does not exist in the glTF file.", target)),
+    }
 }
@@ -0,0 +1,234 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::managers::{Manager, PacketTarget};
+use std::collections::HashMap;
+
+/// Rebuilds the packet list from the packets at the `kept` indices, in that order, and points
+/// every reference at the new index of its packet. References to a packet that is not kept
+/// follow `redirect` (old index to the old index of a kept packet) or are dropped.
+///
+/// Returns the objects whose reference was dropped.
+pub fn compact_packets(
+    manager: &mut dyn Manager,
+    kept: &[usize],
+    redirect: &HashMap<usize, usize>,
+) -> Result<Vec<PacketTarget>, String> {
+    let packets = manager.packets_mut()?;
+    let new_index: HashMap<usize, u64> = kept.iter().enumerate().map(|(new, old)| (*old, new as u64)).collect();
+
+    let old_packets = std::mem::take(packets);
+    *packets = kept
+        .iter()
+        .map(|i| {
+            old_packets
+                .get(*i)
+                .cloned()
+                .ok_or_else(|| format!("Packet {} does not exist.", i))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut dropped = vec![];
+    for (target, packet) in manager.get_packet_references() {
+        let packet = packet as usize;
+        let packet = redirect.get(&packet).unwrap_or(&packet);
+
+        match new_index.get(packet) {
+            Some(index) => manager.set_packet_reference(&target, *index)?,
+            None => {
+                manager.clear_packet_reference(&target)?;
+                dropped.push(target);
+            }
+        }
+    }
+
+    Ok(dropped)
+}
+
+/// Removes a single packet and renumbers the references to later packets. References to the
+/// removed packet are pointed at `reassign_to` (an index before removal) or dropped.
+///
+/// Returns the objects that referenced the removed packet.
+pub fn remove_packet(
+    manager: &mut dyn Manager,
+    packet: usize,
+    reassign_to: Option<usize>,
+) -> Result<Vec<PacketTarget>, String> {
+    let count = manager.packets_mut()?.len();
+    for index in std::iter::once(packet).chain(reassign_to) {
+        if index >= count {
+            return Err(format!(
+                "Packet {} does not exist. The metadata only contains {} packet(s).",
+                index, count
+            ));
+        }
+    }
+    if reassign_to == Some(packet) {
+        return Err(format!("Packet {} cannot be reassigned to itself.", packet));
+    }
+
+    let affected = manager
+        .get_packet_references()
+        .into_iter()
+        .filter(|(_, p)| *p as usize == packet)
+        .map(|(target, _)| target)
+        .collect();
+
+    let kept: Vec<usize> = (0..count).filter(|i| *i != packet).collect();
+    let redirect = reassign_to.map(|m| (packet, m)).into_iter().collect();
+    compact_packets(manager, &kept, &redirect)?;
+
+    Ok(affected)
+}
+
+/// The packets removed by `collect_garbage`, as indices before removal.
+#[derive(Debug, PartialEq)]
+pub struct GarbageReport {
+    /// Packets that no object references.
+    pub unreferenced: Vec<usize>,
+    /// Packets identical to an earlier packet, along with that packet.
+    pub duplicates: Vec<(usize, usize)>,
+    /// Objects whose reference pointed past the last packet and was dropped.
+    pub dangling: Vec<PacketTarget>,
+}
+
+/// Removes packets that no object references and merges structurally identical packets into the
+/// first of them, then compacts the remaining indices. References to packets that do not exist
+/// are dropped.
+pub fn collect_garbage(manager: &mut dyn Manager) -> Result<GarbageReport, String> {
+    let packets = manager.packets_mut()?.clone();
+
+    // The first packet identical to each packet. JSON objects compare regardless of key order.
+    let survivors: Vec<usize> = packets
+        .iter()
+        .enumerate()
+        .map(|(i, packet)| packets[..i].iter().position(|p| p == packet).unwrap_or(i))
+        .collect();
+
+    let referenced: Vec<usize> = manager
+        .get_packet_references()
+        .into_iter()
+        .filter_map(|(_, p)| survivors.get(p as usize).cloned())
+        .collect();
+
+    let mut report = GarbageReport {
+        unreferenced: vec![],
+        duplicates: vec![],
+        dangling: vec![],
+    };
+    let mut kept = vec![];
+    let mut redirect = HashMap::new();
+    for (index, survivor) in survivors.into_iter().enumerate() {
+        if !referenced.contains(&survivor) {
+            report.unreferenced.push(index);
+        } else if survivor != index {
+            report.duplicates.push((index, survivor));
+            redirect.insert(index, survivor);
+        } else {
+            kept.push(index);
+        }
+    }
+
+    report.dangling = compact_packets(manager, &kept, &redirect)?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::managers::khr_xmp_json_ld_manager::KhrXmpJsonLdManager;
+    use serde_json::json;
+
+    fn manager() -> KhrXmpJsonLdManager {
+        KhrXmpJsonLdManager::new(
+            serde_json::from_value(json!({
+                "asset": { "version": "2.0", "extensions": { "KHR_xmp_json_ld": { "packet": 0 } } },
+                "extensions": {
+                    "KHR_xmp_json_ld": { "packets": [{ "dc:title": "A" }, { "dc:title": "B" }, { "dc:title": "C" }] }
+                },
+                "nodes": [
+                    { "extensions": { "KHR_xmp_json_ld": { "packet": 1 } } },
+                    { "extensions": { "KHR_xmp_json_ld": { "packet": 2 } } }
+                ]
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn removes_and_renumbers() {
+        let mut manager = manager();
+
+        let affected = remove_packet(&mut manager, 1, None).unwrap();
+        assert_eq!(affected, vec![PacketTarget::Nodes(0)]);
+        assert_eq!(manager.get_packets().unwrap(), vec![json!({ "dc:title": "A" }), json!({ "dc:title": "C" })]);
+        assert_eq!(
+            manager.get_packet_references(),
+            vec![(PacketTarget::Asset, 0), (PacketTarget::Nodes(1), 1)]
+        );
+    }
+
+    #[test]
+    fn reassigns_references() {
+        let mut manager = manager();
+
+        remove_packet(&mut manager, 0, Some(2)).unwrap();
+        assert_eq!(
+            manager.get_packet_references(),
+            vec![
+                (PacketTarget::Asset, 1),
+                (PacketTarget::Nodes(0), 0),
+                (PacketTarget::Nodes(1), 1)
+            ]
+        );
+
+        assert!(remove_packet(&mut manager, 5, None).is_err());
+        assert!(remove_packet(&mut manager, 0, Some(0)).is_err());
+    }
+
+    #[test]
+    fn collects_unreferenced_and_duplicate_packets() {
+        let mut manager = KhrXmpJsonLdManager::new(
+            serde_json::from_value(json!({
+                "asset": { "version": "2.0", "extensions": { "KHR_xmp_json_ld": { "packet": 3 } } },
+                "extensions": {
+                    "KHR_xmp_json_ld": {
+                        "packets": [
+                            { "dc:title": "Unused" },
+                            { "dc:title": "A", "dc:format": "model/gltf+json" },
+                            { "dc:title": "B" },
+                            { "dc:format": "model/gltf+json", "dc:title": "A" }
+                        ]
+                    }
+                },
+                "nodes": [
+                    { "extensions": { "KHR_xmp_json_ld": { "packet": 1 } } },
+                    { "extensions": { "KHR_xmp_json_ld": { "packet": 2 } } },
+                    { "extensions": { "KHR_xmp_json_ld": { "packet": 9 } } }
+                ]
+            }))
+            .unwrap(),
+        );
+
+        let report = collect_garbage(&mut manager).unwrap();
+        assert_eq!(
+            report,
+            GarbageReport {
+                unreferenced: vec![0],
+                duplicates: vec![(3, 1)],
+                dangling: vec![PacketTarget::Nodes(2)],
+            }
+        );
+        assert_eq!(manager.get_packets().unwrap().len(), 2);
+        assert_eq!(
+            manager.get_packet_references(),
+            vec![
+                (PacketTarget::Asset, 0),
+                (PacketTarget::Nodes(0), 0),
+                (PacketTarget::Nodes(1), 1)
+            ]
+        );
+    }
+}
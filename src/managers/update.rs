@@ -0,0 +1,252 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::xmp::alternatives::{alternatives, is_alt, language_of, same_language, set_alternatives};
+use serde_json::{Map, Value};
+
+/// How new metadata is combined with the metadata already in the glTF file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UpdateMode {
+    /// Existing packets and every packet reference are discarded.
+    Replace,
+    /// New packets are added after the existing ones. Existing references are kept.
+    Append,
+    /// New packet N is merged into existing packet N. Existing references are kept.
+    Merge,
+}
+
+impl UpdateMode {
+    pub fn parse(name: &str) -> Option<UpdateMode> {
+        match name {
+            "replace" => Some(UpdateMode::Replace),
+            "append" => Some(UpdateMode::Append),
+            "merge" => Some(UpdateMode::Merge),
+            _ => None,
+        }
+    }
+}
+
+/// Combines new packets with the existing ones. Returns the index of the first new packet in the
+/// combined list, which selectors must be offset by.
+pub fn combine_packets(mode: UpdateMode, existing: &mut Vec<Value>, incoming: Vec<Value>) -> Result<u64, String> {
+    match mode {
+        UpdateMode::Replace => {
+            *existing = incoming;
+            Ok(0)
+        }
+        UpdateMode::Append => {
+            let offset = existing.len() as u64;
+            existing.extend(incoming);
+            Ok(offset)
+        }
+        UpdateMode::Merge => {
+            for (index, packet) in incoming.into_iter().enumerate() {
+                match existing.get_mut(index) {
+                    Some(e) => merge_value(e, packet)?,
+                    None => existing.push(packet),
+                }
+            }
+            Ok(0)
+        }
+    }
+}
+
+/// Combines the global `@context` of `KHR_xmp` metadata, or the `@context` of a packet being
+/// merged. Unless replacing, a prefix may only be added if it is not already declared with a
+/// different IRI, since existing values rely on it.
+pub fn combine_contexts(mode: UpdateMode, existing: &mut Value, incoming: Value) -> Result<(), String> {
+    if mode == UpdateMode::Replace {
+        *existing = incoming;
+        return Ok(());
+    }
+
+    let (existing, incoming) = match (existing, incoming) {
+        (Value::Object(e), Value::Object(i)) => (e, i),
+        _ => return Err("A @context must be a JSON object to be combined.".to_string()),
+    };
+
+    for (prefix, iri) in incoming {
+        match existing.get(&prefix) {
+            Some(current) if *current != iri => {
+                return Err(format!(
+                    "Prefix \"{}\" is declared as {} in the glTF file but as {} in the new metadata.",
+                    prefix, current, iri
+                ))
+            }
+            Some(_) => {}
+            None => {
+                existing.insert(prefix, iri);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The shape of a JSON-LD value, which decides how two values are merged.
+#[derive(PartialEq)]
+enum Kind {
+    Set,
+    Alt,
+    Structure,
+    Other,
+}
+
+fn kind(value: &Value) -> Kind {
+    let object = match value {
+        Value::Object(o) => o,
+        _ => return Kind::Other,
+    };
+
+    if object.get("@set").is_some_and(Value::is_array) {
+        Kind::Set
+    } else if is_alt(object) {
+        Kind::Alt
+    } else if object.contains_key("@list") || object.contains_key("@value") {
+        Kind::Other
+    } else {
+        Kind::Structure
+    }
+}
+
+/// Merges `incoming` into `existing`:
+///  - `@set` values are unioned.
+///  - `rdf:Alt` values are merged by `@language`.
+///  - Structures (including packets) are merged property by property.
+///  - `@context` objects are combined, failing on a prefix bound to a different IRI.
+///  - Anything else is overridden by the incoming value.
+pub fn merge_value(existing: &mut Value, incoming: Value) -> Result<(), String> {
+    match (kind(existing), kind(&incoming)) {
+        (Kind::Set, Kind::Set) => {
+            let items = existing["@set"].as_array_mut().unwrap();
+            if let Value::Array(incoming) = &incoming["@set"] {
+                for item in incoming {
+                    if !items.contains(item) {
+                        items.push(item.clone());
+                    }
+                }
+            }
+        }
+        (Kind::Alt, Kind::Alt) => merge_alternatives(existing.as_object_mut().unwrap(), incoming),
+        (Kind::Structure, Kind::Structure) => {
+            let existing = existing.as_object_mut().unwrap();
+            if let Value::Object(incoming) = incoming {
+                for (key, value) in incoming {
+                    match existing.get_mut(&key) {
+                        Some(e) if key == "@context" => combine_contexts(UpdateMode::Merge, e, value)?,
+                        Some(e) => merge_value(e, value)?,
+                        None => {
+                            existing.insert(key, value);
+                        }
+                    }
+                }
+            }
+        }
+        _ => *existing = incoming,
+    }
+
+    Ok(())
+}
+
+/// Replaces alternatives that share a `@language` and adds the rest, renumbering `rdf:_N`.
+fn merge_alternatives(existing: &mut Map<String, Value>, incoming: Value) {
+    let mut items: Vec<Value> = alternatives(existing).into_iter().cloned().collect();
+    if let Value::Object(incoming) = incoming {
+        for item in alternatives(&incoming) {
+            let current = language_of(item).and_then(|l| items.iter_mut().find(|i| same_language(i, l)));
+            match current {
+                Some(current) => *current = item.clone(),
+                None => items.push(item.clone()),
+            }
+        }
+    }
+
+    set_alternatives(existing, items);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn appends_after_existing_packets() {
+        let mut packets = vec![json!({ "dc:title": "A" })];
+        let offset = combine_packets(UpdateMode::Append, &mut packets, vec![json!({ "dc:title": "B" })]).unwrap();
+
+        assert_eq!(offset, 1);
+        assert_eq!(packets.len(), 2);
+    }
+
+    #[test]
+    fn merges_packets() {
+        let mut packets = vec![json!({
+            "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+            "dc:format": "model/gltf+json",
+            "dc:subject": { "@set": ["a", "b"] },
+            "dc:title": {
+                "@type": "rdf:Alt",
+                "rdf:_1": { "@language": "x-default", "@value": "Old" },
+                "rdf:_2": { "@language": "it-IT", "@value": "Vecchio" }
+            }
+        })];
+        let incoming = vec![
+            json!({
+                "@context": { "xmp": "http://ns.adobe.com/xap/1.0/" },
+                "dc:format": "model/gltf-binary",
+                "dc:subject": { "@set": ["b", "c"] },
+                "dc:title": {
+                    "@type": "rdf:Alt",
+                    "rdf:_1": { "@language": "de-DE", "@value": "Neu" },
+                    "rdf:_2": { "@language": "x-default", "@value": "New" }
+                },
+                "xmp:Rating": 5
+            }),
+            json!({ "dc:title": "Second" }),
+        ];
+
+        assert_eq!(combine_packets(UpdateMode::Merge, &mut packets, incoming).unwrap(), 0);
+        assert_eq!(
+            packets[0],
+            json!({
+                "@context": {
+                    "dc": "http://purl.org/dc/elements/1.1/",
+                    "xmp": "http://ns.adobe.com/xap/1.0/"
+                },
+                "dc:format": "model/gltf-binary",
+                "dc:subject": { "@set": ["a", "b", "c"] },
+                "dc:title": {
+                    "@type": "rdf:Alt",
+                    "rdf:_1": { "@language": "x-default", "@value": "New" },
+                    "rdf:_2": { "@language": "it-IT", "@value": "Vecchio" },
+                    "rdf:_3": { "@language": "de-DE", "@value": "Neu" }
+                },
+                "xmp:Rating": 5
+            })
+        );
+        assert_eq!(packets[1], json!({ "dc:title": "Second" }));
+    }
+
+    #[test]
+    fn merges_languages_case_insensitively() {
+        let mut title = json!({ "@type": "rdf:Alt", "rdf:_1": { "@language": "en-US", "@value": "Old" } });
+        let incoming = json!({ "@type": "rdf:Alt", "rdf:_1": { "@language": "en-us", "@value": "New" } });
+
+        merge_value(&mut title, incoming).unwrap();
+        assert_eq!(title, json!({ "@type": "rdf:Alt", "rdf:_1": { "@language": "en-us", "@value": "New" } }));
+    }
+
+    #[test]
+    fn rejects_conflicting_prefixes() {
+        let mut context = json!({ "dc": "http://purl.org/dc/elements/1.1/" });
+
+        assert!(combine_contexts(UpdateMode::Append, &mut context, json!({ "dc": "http://example.com/" })).is_err());
+        assert!(combine_contexts(UpdateMode::Merge, &mut context, json!({ "xmp": "http://ns.adobe.com/xap/1.0/" })).is_ok());
+        assert_eq!(context["xmp"], json!("http://ns.adobe.com/xap/1.0/"));
+
+        let mut packets = vec![json!({ "@context": { "dc": "http://purl.org/dc/elements/1.1/" }, "dc:title": "A" })];
+        let incoming = vec![json!({ "@context": { "dc": "http://example.com/" }, "dc:title": "B" })];
+        assert!(combine_packets(UpdateMode::Merge, &mut packets, incoming).is_err());
+    }
+}
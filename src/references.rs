@@ -0,0 +1,317 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Follows the index references between glTF objects, e.g. from a node to its children and mesh.
+//! References to objects that do not exist are ignored.
+
+use crate::json_models::extension::ExtensionsOnly;
+use crate::json_models::gltf::Gltf;
+use crate::managers::PacketTarget;
+use serde_json::Value;
+
+fn get(objects: &Option<Vec<ExtensionsOnly>>, index: usize) -> Option<&ExtensionsOnly> {
+    objects.as_ref().and_then(|o| o.get(index))
+}
+
+fn as_index(value: &Value, count: usize) -> Option<usize> {
+    value.as_u64().map(|i| i as usize).filter(|i| *i < count)
+}
+
+fn count(objects: &Option<Vec<ExtensionsOnly>>) -> usize {
+    objects.as_ref().map_or(0, |o| o.len())
+}
+
+/// Returns the extension objects of a glTF object other than KHR_xmp and KHR_xmp_json_ld.
+fn other_extensions(object: &ExtensionsOnly) -> impl Iterator<Item = &Value> {
+    object
+        .extensions
+        .iter()
+        .flat_map(|e| e.other_extensions.values())
+}
+
+/// Returns the indices of the direct children of a node.
+pub fn node_children(gltf: &Gltf, node: usize) -> Vec<usize> {
+    let node_count = count(&gltf.nodes);
+
+    get(&gltf.nodes, node)
+        .and_then(|n| n.other_fields.get("children"))
+        .and_then(Value::as_array)
+        .map(|children| children.iter().filter_map(|c| as_index(c, node_count)).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the given nodes and all of their descendants, in depth-first order and without
+/// duplicates. Cycles in invalid files are ignored.
+pub fn descendants(gltf: &Gltf, roots: &[usize]) -> Vec<usize> {
+    let mut visited = vec![];
+    let mut stack: Vec<usize> = roots.iter().rev().cloned().collect();
+
+    while let Some(node) = stack.pop() {
+        if visited.contains(&node) {
+            continue;
+        }
+        visited.push(node);
+        stack.extend(node_children(gltf, node).into_iter().rev());
+    }
+
+    visited
+}
+
+/// Returns the mesh instantiated by a node, if any.
+pub fn node_mesh(gltf: &Gltf, node: usize) -> Option<usize> {
+    get(&gltf.nodes, node)
+        .and_then(|n| n.other_fields.get("mesh"))
+        .and_then(|m| as_index(m, count(&gltf.meshes)))
+}
+
+/// Returns the materials used by the primitives of a mesh.
+pub fn mesh_materials(gltf: &Gltf, mesh: usize) -> Vec<usize> {
+    let material_count = count(&gltf.materials);
+    let mut materials = vec![];
+
+    let primitives = get(&gltf.meshes, mesh)
+        .and_then(|m| m.other_fields.get("primitives"))
+        .and_then(Value::as_array);
+    for primitive in primitives.into_iter().flatten() {
+        if let Some(material) = primitive.get("material").and_then(|m| as_index(m, material_count)) {
+            if !materials.contains(&material) {
+                materials.push(material);
+            }
+        }
+    }
+
+    materials
+}
+
+/// Returns the textures used by a material. Every texture info (an object with an `index` under
+/// a property ending in `Texture`) is followed, including those of material extensions.
+pub fn material_textures(gltf: &Gltf, material: usize) -> Vec<usize> {
+    let texture_count = count(&gltf.textures);
+    let mut textures = vec![];
+
+    if let Some(material) = get(&gltf.materials, material) {
+        let extension_fields = other_extensions(material).filter_map(Value::as_object).flatten();
+        for (key, value) in material.other_fields.iter().chain(extension_fields) {
+            collect_texture_infos(key, value, texture_count, &mut textures);
+        }
+    }
+
+    // Material properties are unordered, so sort to keep the output stable.
+    textures.sort_unstable();
+    textures
+}
+
+fn collect_texture_infos(key: &str, value: &Value, texture_count: usize, textures: &mut Vec<usize>) {
+    let object = match value {
+        Value::Object(o) => o,
+        _ => return,
+    };
+
+    if key.ends_with("Texture") {
+        if let Some(texture) = object.get("index").and_then(|i| as_index(i, texture_count)) {
+            if !textures.contains(&texture) {
+                textures.push(texture);
+            }
+        }
+    }
+
+    for (key, value) in object {
+        collect_texture_infos(key, value, texture_count, textures);
+    }
+}
+
+/// Returns the images a texture samples: its `source` and the sources of texture extensions
+/// such as `KHR_texture_basisu`.
+pub fn texture_images(gltf: &Gltf, texture: usize) -> Vec<usize> {
+    let image_count = count(&gltf.images);
+    let texture = match get(&gltf.textures, texture) {
+        Some(t) => t,
+        None => return vec![],
+    };
+
+    let mut images = vec![];
+    let sources = texture
+        .other_fields
+        .get("source")
+        .into_iter()
+        .chain(other_extensions(texture).filter_map(|e| e.get("source")));
+    for source in sources {
+        if let Some(image) = as_index(source, image_count) {
+            if !images.contains(&image) {
+                images.push(image);
+            }
+        }
+    }
+
+    images
+}
+
+/// Returns the images used by a material, through its textures.
+pub fn material_images(gltf: &Gltf, material: usize) -> Vec<usize> {
+    let mut images = vec![];
+    for texture in material_textures(gltf, material) {
+        for image in texture_images(gltf, texture) {
+            if !images.contains(&image) {
+                images.push(image);
+            }
+        }
+    }
+
+    images
+}
+
+/// Returns the materials that sample an image through one of their textures.
+pub fn image_materials(gltf: &Gltf, image: usize) -> Vec<usize> {
+    (0..count(&gltf.materials))
+        .filter(|m| material_images(gltf, *m).contains(&image))
+        .collect()
+}
+
+/// Returns the root nodes of a scene.
+pub fn scene_nodes(gltf: &Gltf, scene: usize) -> Vec<usize> {
+    let node_count = count(&gltf.nodes);
+
+    get(&gltf.scenes, scene)
+        .and_then(|s| s.other_fields.get("nodes"))
+        .and_then(Value::as_array)
+        .map(|nodes| nodes.iter().filter_map(|n| as_index(n, node_count)).collect())
+        .unwrap_or_default()
+}
+
+/// Expands targets through the objects they depend on:
+///  - scenes include their nodes and all of their descendants,
+///  - nodes include their mesh,
+///  - meshes include the materials of their primitives,
+///  - materials include the images of their textures,
+///  - images include the materials that use them, but not the other images of those materials.
+///
+/// The given targets come first, followed by the added nodes, meshes, materials and images.
+pub fn with_dependencies(gltf: &Gltf, targets: &[PacketTarget]) -> Vec<PacketTarget> {
+    let mut nodes = vec![];
+    let mut meshes = vec![];
+    let mut materials = vec![];
+    let mut images = vec![];
+    // Materials found through their images, which are not expanded back into their other images.
+    let mut image_users = vec![];
+
+    for target in targets {
+        match target {
+            PacketTarget::Scenes(scene) => nodes.extend(descendants(gltf, &scene_nodes(gltf, *scene))),
+            PacketTarget::Nodes(node) => nodes.push(*node),
+            PacketTarget::Meshes(mesh) => meshes.push(*mesh),
+            PacketTarget::Materials(material) => materials.push(*material),
+            PacketTarget::Images(image) => {
+                images.push(*image);
+                image_users.extend(image_materials(gltf, *image));
+            }
+            PacketTarget::Asset | PacketTarget::Animations(_) => {}
+        }
+    }
+
+    meshes.extend(nodes.iter().filter_map(|n| node_mesh(gltf, *n)));
+    materials.extend(meshes.iter().flat_map(|m| mesh_materials(gltf, *m)));
+    images.extend(materials.iter().flat_map(|m| material_images(gltf, *m)));
+    materials.extend(image_users);
+
+    let mut expanded = targets.to_vec();
+    let added = nodes
+        .into_iter()
+        .map(PacketTarget::Nodes)
+        .chain(meshes.into_iter().map(PacketTarget::Meshes))
+        .chain(materials.into_iter().map(PacketTarget::Materials))
+        .chain(images.into_iter().map(PacketTarget::Images));
+    for target in added {
+        if !expanded.contains(&target) {
+            expanded.push(target);
+        }
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gltf() -> Gltf {
+        serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "nodes": [
+                { "children": [1, 3] },
+                { "children": [2], "mesh": 0 },
+                { "mesh": 1 },
+                { "children": [0] },
+                {}
+            ],
+            "meshes": [
+                { "primitives": [{ "material": 0 }, { "material": 0 }] },
+                { "primitives": [{ "material": 1 }, { "material": 7 }] }
+            ],
+            "materials": [
+                { "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } }, "normalTexture": { "index": 1 } },
+                {
+                    "extensions": {
+                        "KHR_materials_clearcoat": { "clearcoatTexture": { "index": 2 } }
+                    }
+                }
+            ],
+            "textures": [
+                { "source": 0 },
+                { "source": 1 },
+                { "extensions": { "KHR_texture_basisu": { "source": 2 } } }
+            ],
+            "images": [{}, {}, {}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn walks_node_hierarchy() {
+        let gltf = gltf();
+
+        assert_eq!(descendants(&gltf, &[0]), vec![0, 1, 2, 3]);
+        assert_eq!(descendants(&gltf, &[1, 4]), vec![1, 2, 4]);
+        assert_eq!(node_children(&gltf, 2), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn follows_meshes_materials_and_textures() {
+        let gltf = gltf();
+
+        assert_eq!(node_mesh(&gltf, 2), Some(1));
+        assert_eq!(mesh_materials(&gltf, 0), vec![0]);
+        assert_eq!(mesh_materials(&gltf, 1), vec![1]);
+        assert_eq!(material_images(&gltf, 0), vec![0, 1]);
+        assert_eq!(material_images(&gltf, 1), vec![2]);
+        assert_eq!(image_materials(&gltf, 2), vec![1]);
+    }
+
+    #[test]
+    fn expands_dependencies() {
+        let gltf = gltf();
+
+        assert_eq!(
+            with_dependencies(&gltf, &[PacketTarget::Nodes(1)]),
+            vec![
+                PacketTarget::Nodes(1),
+                PacketTarget::Meshes(0),
+                PacketTarget::Materials(0),
+                PacketTarget::Images(0),
+                PacketTarget::Images(1)
+            ]
+        );
+        assert_eq!(
+            with_dependencies(&gltf, &[PacketTarget::Images(2)]),
+            vec![PacketTarget::Images(2), PacketTarget::Materials(1)]
+        );
+        assert_eq!(
+            with_dependencies(&gltf, &[PacketTarget::Images(0)]),
+            vec![PacketTarget::Images(0), PacketTarget::Materials(0)]
+        );
+        assert_eq!(
+            with_dependencies(&gltf, &[PacketTarget::Asset]),
+            vec![PacketTarget::Asset]
+        );
+    }
+}
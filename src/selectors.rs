@@ -0,0 +1,622 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::json_models::extension::ExtensionsOnly;
+use crate::json_models::gltf::Gltf;
+use crate::managers::PacketTarget;
+use crate::references::{descendants, with_dependencies};
+use crate::PacketApplied;
+use glob::Pattern;
+use regex::Regex;
+use std::fmt;
+
+/// A category of glTF objects that may reference a metadata packet.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Category {
+    Animations,
+    Images,
+    Materials,
+    Meshes,
+    Nodes,
+    Scenes,
+}
+
+impl Category {
+    fn parse(name: &str) -> Option<Category> {
+        match name {
+            "animations" => Some(Category::Animations),
+            "images" => Some(Category::Images),
+            "materials" => Some(Category::Materials),
+            "meshes" => Some(Category::Meshes),
+            "nodes" => Some(Category::Nodes),
+            "scenes" => Some(Category::Scenes),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Animations => "animations",
+            Category::Images => "images",
+            Category::Materials => "materials",
+            Category::Meshes => "meshes",
+            Category::Nodes => "nodes",
+            Category::Scenes => "scenes",
+        }
+    }
+
+    /// The category and index of an object, or `None` for the asset.
+    pub fn of(target: &PacketTarget) -> Option<(Category, usize)> {
+        match *target {
+            PacketTarget::Asset => None,
+            PacketTarget::Animations(i) => Some((Category::Animations, i)),
+            PacketTarget::Images(i) => Some((Category::Images, i)),
+            PacketTarget::Materials(i) => Some((Category::Materials, i)),
+            PacketTarget::Meshes(i) => Some((Category::Meshes, i)),
+            PacketTarget::Nodes(i) => Some((Category::Nodes, i)),
+            PacketTarget::Scenes(i) => Some((Category::Scenes, i)),
+        }
+    }
+
+    pub fn target(self, index: usize) -> PacketTarget {
+        match self {
+            Category::Animations => PacketTarget::Animations(index),
+            Category::Images => PacketTarget::Images(index),
+            Category::Materials => PacketTarget::Materials(index),
+            Category::Meshes => PacketTarget::Meshes(index),
+            Category::Nodes => PacketTarget::Nodes(index),
+            Category::Scenes => PacketTarget::Scenes(index),
+        }
+    }
+
+    pub fn objects(self, gltf: &Gltf) -> &[ExtensionsOnly] {
+        let objects = match self {
+            Category::Animations => &gltf.animations,
+            Category::Images => &gltf.images,
+            Category::Materials => &gltf.materials,
+            Category::Meshes => &gltf.meshes,
+            Category::Nodes => &gltf.nodes,
+            Category::Scenes => &gltf.scenes,
+        };
+
+        objects.as_deref().unwrap_or(&[])
+    }
+}
+
+/// A single index or a range of indices within a category. Ranges follow Rust syntax: `0..4`
+/// excludes 4, while `0..=4` includes it.
+#[derive(Clone, PartialEq, Debug)]
+pub enum IndexRange {
+    Single(usize),
+    Range(usize, usize),
+}
+
+type NameMatcher = Box<dyn Fn(&str) -> bool>;
+
+/// A pattern matched against the `name` of glTF objects.
+#[derive(Clone, PartialEq, Debug)]
+pub enum NamePattern {
+    /// Shell-style glob, e.g. `Wheel_*`.
+    Glob(String),
+    /// Regular expression, e.g. `^Metal`.
+    Regex(String),
+}
+
+impl NamePattern {
+    fn matcher(&self) -> Result<NameMatcher, String> {
+        match self {
+            NamePattern::Glob(glob) => {
+                let pattern = Pattern::new(glob).map_err(|e| format!("invalid glob \"{}\": {}", glob, e))?;
+                Ok(Box::new(move |name| pattern.matches(name)))
+            }
+            NamePattern::Regex(regex) => {
+                let regex = Regex::new(regex).map_err(|e| format!("invalid regex \"{}\": {}", regex, e))?;
+                Ok(Box::new(move |name| regex.is_match(name)))
+            }
+        }
+    }
+}
+
+/// The objects a packet should be applied to.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ObjectSelector {
+    Asset,
+    /// Every object of the category.
+    All(Category),
+    Indices(Category, Vec<IndexRange>),
+    /// Every object of the category whose name matches the pattern.
+    Name(Category, NamePattern),
+    /// The selected nodes and all of their descendants. With `resources`, the meshes, materials
+    /// and images used by those nodes are included as well.
+    Subtree {
+        roots: Box<ObjectSelector>,
+        resources: bool,
+    },
+}
+
+/// A parsed `--apply` value, e.g. `packet=1:nodes[3,5]`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Selector {
+    pub packet: u64,
+    pub objects: ObjectSelector,
+    /// Whether the objects are expanded through the objects they reference (`+deps`).
+    pub dependencies: bool,
+    text: String,
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl Selector {
+    /// The selector used when none are given: the first packet applied to the asset.
+    pub fn default_asset() -> Selector {
+        Selector {
+            packet: 0,
+            objects: ObjectSelector::Asset,
+            dependencies: false,
+            text: "packet=0:asset".to_string(),
+        }
+    }
+
+    /// Whether the selector discovers its objects by name or through the node hierarchy, rather
+    /// than addressing them directly. Such selectors report how many objects they matched.
+    pub fn reports_matches(&self) -> bool {
+        self.dependencies
+            || matches!(
+                self.objects,
+                ObjectSelector::Name(_, _) | ObjectSelector::Subtree { .. }
+            )
+    }
+}
+
+/// Parses a selector of the form `packet=<N>:<objects>`, where `<objects>` is one of:
+///  - `asset`
+///  - `<category>` for every object in the category, e.g. `nodes`
+///  - `<category>[<indices>]`, e.g. `nodes[3,5]` or `materials[0..4]`
+///  - `<category>/name=<glob>`, e.g. `nodes/name=Wheel_*`
+///  - `<category>/name~/<regex>/`, e.g. `materials/name~/^Metal/`
+///  - `subtree(<nodes>)`, e.g. `subtree(nodes[7])`, or `subtree(<nodes>, resources)` to also
+///    include the meshes, materials and images used by the subtree
+///
+/// Any of these may end in `+deps` to include the objects they reference, e.g.
+/// `materials[1]+deps`. See `references::with_dependencies`.
+pub fn parse_selector(text: &str) -> Result<Selector, String> {
+    let invalid = |reason: &str| format!("Invalid selector \"{}\": {}", text, reason);
+
+    let (packet, objects) = match text.strip_prefix("packet=").and_then(|t| t.split_once(':')) {
+        Some(parts) => parts,
+        None => return Err(invalid("expected packet=<N>:<objects>.")),
+    };
+
+    let packet = packet
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| invalid("the packet must be a non-negative integer."))?;
+
+    let (objects, dependencies) = parse_targets(objects).map_err(|reason| invalid(&reason))?;
+
+    Ok(Selector {
+        packet,
+        objects,
+        dependencies,
+        text: text.to_string(),
+    })
+}
+
+/// Parses the object part of a selector on its own, e.g. `nodes[3]`, to find objects rather than
+/// to apply a packet to them. The packet of the returned selector is unused.
+pub fn parse_object_selector(text: &str) -> Result<Selector, String> {
+    let (objects, dependencies) =
+        parse_targets(text).map_err(|reason| format!("Invalid object selector \"{}\": {}", text, reason))?;
+
+    Ok(Selector {
+        packet: 0,
+        objects,
+        dependencies,
+        text: text.to_string(),
+    })
+}
+
+/// Parses objects with an optional `+deps` suffix.
+fn parse_targets(text: &str) -> Result<(ObjectSelector, bool), String> {
+    let (objects, dependencies) = match text.trim().strip_suffix("+deps") {
+        Some(objects) => (objects, true),
+        None => (text.trim(), false),
+    };
+
+    Ok((parse_objects(objects)?, dependencies))
+}
+
+fn parse_objects(text: &str) -> Result<ObjectSelector, String> {
+    if text == "asset" {
+        return Ok(ObjectSelector::Asset);
+    }
+
+    if let Some(inner) = text.strip_prefix("subtree(").and_then(|t| t.strip_suffix(')')) {
+        let (roots, resources) = match inner.rsplit_once(',') {
+            Some((roots, option)) if option.trim() == "resources" => (roots.trim(), true),
+            _ => (inner.trim(), false),
+        };
+
+        let roots = parse_objects(roots)?;
+        return match roots {
+            ObjectSelector::All(Category::Nodes)
+            | ObjectSelector::Indices(Category::Nodes, _)
+            | ObjectSelector::Name(Category::Nodes, _) => Ok(ObjectSelector::Subtree {
+                roots: Box::new(roots),
+                resources,
+            }),
+            _ => Err("subtree() expects nodes, e.g. subtree(nodes[7]).".to_string()),
+        };
+    }
+
+    if let Some((name, pattern)) = text.split_once("/name") {
+        let category = parse_category(name)?;
+        let pattern = if let Some(glob) = pattern.strip_prefix('=') {
+            NamePattern::Glob(glob.to_string())
+        } else if let Some(regex) = pattern.strip_prefix("~/").and_then(|r| r.strip_suffix('/')) {
+            NamePattern::Regex(regex.to_string())
+        } else {
+            return Err("expected name=<glob> or name~/<regex>/.".to_string());
+        };
+
+        // Compile once up front so that bad patterns are reported before any file is read.
+        let _ = pattern.matcher()?;
+        return Ok(ObjectSelector::Name(category, pattern));
+    }
+
+    let (name, indices) = match text.find('[') {
+        Some(i) => match text[i + 1..].strip_suffix(']') {
+            Some(indices) => (&text[..i], Some(indices)),
+            None => return Err("missing closing \"]\".".to_string()),
+        },
+        None => (text, None),
+    };
+
+    let category = parse_category(name)?;
+
+    match indices {
+        None => Ok(ObjectSelector::All(category)),
+        Some(indices) => Ok(ObjectSelector::Indices(category, parse_indices(indices)?)),
+    }
+}
+
+fn parse_category(name: &str) -> Result<Category, String> {
+    match Category::parse(name) {
+        Some(c) => Ok(c),
+        None => Err(format!(
+            "unknown category \"{}\". Expected asset, animations, images, materials, meshes, nodes or scenes.",
+            name
+        )),
+    }
+}
+
+fn parse_indices(text: &str) -> Result<Vec<IndexRange>, String> {
+    let parse_index = |i: &str| {
+        i.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("\"{}\" is not a valid index.", i.trim()))
+    };
+
+    let mut ranges = vec![];
+    for item in text.split(',') {
+        let range = if let Some((start, end)) = item.split_once("..=") {
+            let start = parse_index(start)?;
+            let end = parse_index(end)?
+                .checked_add(1)
+                .ok_or_else(|| format!("the range \"{}\" ends past the largest index.", item.trim()))?;
+            IndexRange::Range(start, end)
+        } else if let Some((start, end)) = item.split_once("..") {
+            IndexRange::Range(parse_index(start)?, parse_index(end)?)
+        } else {
+            IndexRange::Single(parse_index(item)?)
+        };
+
+        if let IndexRange::Range(start, end) = range {
+            if start >= end {
+                return Err(format!("the range \"{}\" is empty.", item.trim()));
+            }
+        }
+        ranges.push(range);
+    }
+
+    Ok(ranges)
+}
+
+/// Expands selectors into the individual objects they apply to, failing on any index that does
+/// not exist in the glTF document.
+pub fn resolve_selectors(selectors: &[Selector], gltf: &Gltf) -> Result<Vec<PacketApplied>, String> {
+    let mut applied = vec![];
+
+    for selector in selectors {
+        for target in resolve_objects(selector, gltf)? {
+            applied.push(PacketApplied {
+                packet: selector.packet,
+                target,
+            });
+        }
+    }
+
+    Ok(applied)
+}
+
+/// Returns the objects a single selector applies to.
+pub fn resolve_objects(selector: &Selector, gltf: &Gltf) -> Result<Vec<PacketTarget>, String> {
+    let targets = resolve(selector, &selector.objects, gltf)?;
+    if selector.dependencies {
+        return Ok(with_dependencies(gltf, &targets));
+    }
+
+    Ok(targets)
+}
+
+fn resolve(selector: &Selector, objects: &ObjectSelector, gltf: &Gltf) -> Result<Vec<PacketTarget>, String> {
+    let (category, ranges) = match objects {
+        ObjectSelector::Asset => return Ok(vec![PacketTarget::Asset]),
+        ObjectSelector::All(category) => {
+            let count = category.objects(gltf).len();
+            if count == 0 {
+                return Err(format!(
+                    "Selector \"{}\": the glTF file has no {}.",
+                    selector,
+                    category.name()
+                ));
+            }
+            return Ok((0..count).map(|i| category.target(i)).collect());
+        }
+        ObjectSelector::Indices(category, ranges) => (*category, ranges),
+        ObjectSelector::Name(category, pattern) => {
+            let matches = pattern
+                .matcher()
+                .map_err(|e| format!("Selector \"{}\": {}", selector, e))?;
+
+            let targets: Vec<PacketTarget> = category
+                .objects(gltf)
+                .iter()
+                .enumerate()
+                .filter(|(_, object)| object_name(object).is_some_and(&matches))
+                .map(|(i, _)| category.target(i))
+                .collect();
+
+            if targets.is_empty() {
+                return Err(format!(
+                    "Selector \"{}\" did not match any {}.",
+                    selector,
+                    category.name()
+                ));
+            }
+            return Ok(targets);
+        }
+        ObjectSelector::Subtree { roots, resources } => {
+            let roots: Vec<usize> = resolve(selector, roots, gltf)?
+                .into_iter()
+                .filter_map(|target| match target {
+                    PacketTarget::Nodes(i) => Some(i),
+                    _ => None,
+                })
+                .collect();
+
+            let targets: Vec<PacketTarget> = descendants(gltf, &roots)
+                .into_iter()
+                .map(PacketTarget::Nodes)
+                .collect();
+            if *resources {
+                return Ok(with_dependencies(gltf, &targets));
+            }
+            return Ok(targets);
+        }
+    };
+
+    let count = category.objects(gltf).len();
+    let mut targets = vec![];
+    for range in ranges {
+        let (start, last) = match range {
+            IndexRange::Single(i) => (*i, *i),
+            IndexRange::Range(start, end) => (*start, end - 1),
+        };
+
+        let end = match last.checked_add(1) {
+            Some(end) if end <= count => end,
+            _ => {
+                return Err(format!(
+                    "Selector \"{}\": {}[{}] is out of range. The glTF file has {} {}.",
+                    selector,
+                    category.name(),
+                    last,
+                    count,
+                    category.name()
+                ))
+            }
+        };
+
+        for index in start..end {
+            let target = category.target(index);
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Returns the `name` property of a glTF object, if it has one.
+pub fn object_name(object: &ExtensionsOnly) -> Option<&str> {
+    object.other_fields.get("name").and_then(|n| n.as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn gltf() -> Gltf {
+        serde_json::from_value(json!({
+            "asset": { "version": "2.0" },
+            "materials": [{ "name": "Metal_Chrome" }, { "name": "Paint" }, {}, {}, { "name": "Metal_Steel" }],
+            "nodes": [{ "name": "Body" }, { "name": "Wheel_FL" }, { "name": "Wheel_FR" }, {}, {}, {}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_indices_and_ranges() {
+        let selector = parse_selector("packet=1:nodes[3,5]").unwrap();
+        assert_eq!(selector.packet, 1);
+        assert_eq!(
+            selector.objects,
+            ObjectSelector::Indices(Category::Nodes, vec![IndexRange::Single(3), IndexRange::Single(5)])
+        );
+
+        let selector = parse_selector("packet=2:materials[0..4]").unwrap();
+        assert_eq!(
+            selector.objects,
+            ObjectSelector::Indices(Category::Materials, vec![IndexRange::Range(0, 4)])
+        );
+
+        let selector = parse_selector("packet=2:materials[1..=2]").unwrap();
+        assert_eq!(
+            selector.objects,
+            ObjectSelector::Indices(Category::Materials, vec![IndexRange::Range(1, 3)])
+        );
+
+        assert_eq!(parse_selector("packet=0:asset").unwrap().objects, ObjectSelector::Asset);
+        assert_eq!(
+            parse_selector("packet=0:scenes").unwrap().objects,
+            ObjectSelector::All(Category::Scenes)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_selectors() {
+        assert!(parse_selector("nodes[1]").is_err());
+        assert!(parse_selector("packet=x:nodes[1]").is_err());
+        assert!(parse_selector("packet=0:cameras[1]").is_err());
+        assert!(parse_selector("packet=0:nodes[1").is_err());
+        assert!(parse_selector("packet=0:nodes[4..2]").is_err());
+        assert!(parse_selector("packet=0:nodes[0..=18446744073709551615]").is_err());
+    }
+
+    #[test]
+    fn resolves_targets() {
+        let selectors = vec![
+            parse_selector("packet=1:nodes[3,5]").unwrap(),
+            parse_selector("packet=2:materials[0..2]").unwrap(),
+        ];
+
+        let targets: Vec<(u64, PacketTarget)> = resolve_selectors(&selectors, &gltf())
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.packet, a.target))
+            .collect();
+
+        assert_eq!(
+            targets,
+            vec![
+                (1, PacketTarget::Nodes(3)),
+                (1, PacketTarget::Nodes(5)),
+                (2, PacketTarget::Materials(0)),
+                (2, PacketTarget::Materials(1)),
+            ]
+        );
+
+        let largest = parse_selector("packet=0:nodes[18446744073709551615]").unwrap();
+        assert!(resolve_objects(&largest, &gltf()).unwrap_err().contains("out of range"));
+    }
+
+    #[test]
+    fn resolves_name_patterns() {
+        let glob = parse_selector("packet=0:nodes/name=Wheel_*").unwrap();
+        assert_eq!(
+            resolve_objects(&glob, &gltf()).unwrap(),
+            vec![PacketTarget::Nodes(1), PacketTarget::Nodes(2)]
+        );
+
+        let regex = parse_selector("packet=0:materials/name~/^Metal/").unwrap();
+        assert!(regex.reports_matches());
+        assert_eq!(
+            resolve_objects(&regex, &gltf()).unwrap(),
+            vec![PacketTarget::Materials(0), PacketTarget::Materials(4)]
+        );
+
+        let nothing = parse_selector("packet=0:nodes/name=Door*").unwrap();
+        assert!(resolve_objects(&nothing, &gltf()).is_err());
+
+        assert!(parse_selector("packet=0:nodes/name~/[/").is_err());
+        assert!(parse_selector("packet=0:nodes/name~^Metal").is_err());
+    }
+
+    #[test]
+    fn resolves_subtrees() {
+        let gltf: Gltf = serde_json::from_value(json!({
+            "asset": { "version": "2.0" },
+            "nodes": [{ "children": [2] }, {}, { "children": [3], "mesh": 0 }, { "mesh": 0 }],
+            "meshes": [{ "primitives": [{ "material": 0 }] }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+            "textures": [{ "source": 1 }],
+            "images": [{}, {}]
+        }))
+        .unwrap();
+
+        let subtree = parse_selector("packet=0:subtree(nodes[0])").unwrap();
+        assert!(subtree.reports_matches());
+        assert_eq!(
+            resolve_objects(&subtree, &gltf).unwrap(),
+            vec![PacketTarget::Nodes(0), PacketTarget::Nodes(2), PacketTarget::Nodes(3)]
+        );
+
+        let resources = parse_selector("packet=0:subtree(nodes[0,1], resources)").unwrap();
+        assert_eq!(
+            resolve_objects(&resources, &gltf).unwrap(),
+            vec![
+                PacketTarget::Nodes(0),
+                PacketTarget::Nodes(2),
+                PacketTarget::Nodes(3),
+                PacketTarget::Nodes(1),
+                PacketTarget::Meshes(0),
+                PacketTarget::Materials(0),
+                PacketTarget::Images(1)
+            ]
+        );
+
+        assert!(parse_selector("packet=0:subtree(materials[0])").is_err());
+        assert!(parse_selector("packet=0:subtree(nodes[0], everything)").is_err());
+    }
+
+    #[test]
+    fn expands_dependencies() {
+        let gltf: Gltf = serde_json::from_value(json!({
+            "asset": { "version": "2.0" },
+            "materials": [{}, { "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+            "textures": [{ "source": 1 }],
+            "images": [{}, {}]
+        }))
+        .unwrap();
+
+        let selector = parse_selector("packet=2:materials[1]+deps").unwrap();
+        assert!(selector.dependencies);
+        assert_eq!(
+            resolve_objects(&selector, &gltf).unwrap(),
+            vec![PacketTarget::Materials(1), PacketTarget::Images(1)]
+        );
+
+        let reverse = parse_selector("packet=2:images[1]+deps").unwrap();
+        assert_eq!(
+            resolve_objects(&reverse, &gltf).unwrap(),
+            vec![PacketTarget::Images(1), PacketTarget::Materials(1)]
+        );
+    }
+
+    #[test]
+    fn fails_on_out_of_range_or_missing_objects() {
+        let out_of_range = vec![parse_selector("packet=0:nodes[6]").unwrap()];
+        assert!(resolve_selectors(&out_of_range, &gltf()).is_err());
+
+        let missing = vec![parse_selector("packet=0:animations").unwrap()];
+        assert!(resolve_selectors(&missing, &gltf()).is_err());
+    }
+}
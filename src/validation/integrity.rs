@@ -0,0 +1,227 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::json_models::gltf::Gltf;
+use crate::validation::{escape, Finding};
+use serde_json::{Map, Value};
+
+/// The object types that may reference a packet, besides the asset.
+const ALLOWED_CATEGORIES: [&str; 6] = ["animations", "images", "materials", "meshes", "nodes", "scenes"];
+
+struct Checker<'a> {
+    extension: &'a str,
+    packet_count: Option<usize>,
+    findings: Vec<Finding>,
+    /// Whether any object, or the document itself, carries the extension.
+    used: bool,
+}
+
+/// Checks that the packet references of the given extension (`KHR_xmp_json_ld` or `KHR_xmp`)
+/// are consistent with the document:
+///  - every reference points at an existing packet,
+///  - only the asset, animations, images, materials, meshes, nodes and scenes reference packets,
+///  - the extension is listed in `extensionsUsed` and not in `extensionsRequired`.
+pub fn check_references(gltf: &Gltf, extension: &str) -> Result<Vec<Finding>, String> {
+    let document = serde_json::to_value(gltf).map_err(|e| e.to_string())?;
+
+    let packet_count = document
+        .pointer(&format!("/extensions/{}/packets", escape(extension)))
+        .and_then(Value::as_array)
+        .map(|packets| packets.len());
+
+    let mut checker = Checker {
+        extension,
+        packet_count,
+        findings: vec![],
+        used: packet_count.is_some(),
+    };
+    checker.walk(&document, "");
+    checker.check_extension_lists(&document);
+
+    Ok(checker.findings)
+}
+
+impl<'a> Checker<'a> {
+    fn walk(&mut self, value: &Value, path: &str) {
+        match value {
+            Value::Object(object) => {
+                if !path.is_empty() {
+                    self.check_object(object, path);
+                }
+
+                for (key, child) in object {
+                    let child_path = format!("{}/{}", path, escape(key));
+                    // Packets are metadata, not glTF objects.
+                    if child_path == format!("/extensions/{}", escape(self.extension)) {
+                        continue;
+                    }
+                    self.walk(child, &child_path);
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.walk(item, &format!("{}/{}", path, index));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_object(&mut self, object: &Map<String, Value>, path: &str) {
+        let reference = match object.get("extensions").and_then(|e| e.get(self.extension)) {
+            Some(r) => r,
+            None => return,
+        };
+        self.used = true;
+
+        let reference_path = format!("{}/extensions/{}", path, escape(self.extension));
+        if !is_allowed(path) {
+            self.findings.push(Finding::new(
+                &reference_path,
+                format!("{} is not allowed on this object.", self.extension),
+            ));
+            return;
+        }
+
+        let packet_path = format!("{}/packet", reference_path);
+        match (reference.get("packet").map(Value::as_u64), self.packet_count) {
+            (None, _) => self.findings.push(Finding::new(
+                &reference_path,
+                "The packet property is missing.".to_string(),
+            )),
+            (Some(None), _) => self.findings.push(Finding::new(
+                &packet_path,
+                "The packet index must be a non-negative integer.".to_string(),
+            )),
+            (Some(Some(packet)), None) => self.findings.push(Finding::new(
+                &packet_path,
+                format!(
+                    "Packet {} is referenced, but the document has no top-level {} extension.",
+                    packet, self.extension
+                ),
+            )),
+            (Some(Some(packet)), Some(count)) if packet as usize >= count => self.findings.push(Finding::new(
+                &packet_path,
+                format!(
+                    "Packet {} is out of range. The document contains {} packet(s).",
+                    packet, count
+                ),
+            )),
+            _ => {}
+        }
+    }
+
+    fn check_extension_lists(&mut self, document: &Value) {
+        let extension = self.extension;
+        let listed = |list: &str| -> Vec<usize> {
+            document
+                .get(list)
+                .and_then(Value::as_array)
+                .map(|names| {
+                    names
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, name)| name.as_str() == Some(extension))
+                        .map(|(index, _)| index)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        if self.used && listed("extensionsUsed").is_empty() {
+            self.findings.push(Finding::new(
+                "/extensionsUsed",
+                format!("{} is used but not listed in extensionsUsed.", self.extension),
+            ));
+        }
+
+        for index in listed("extensionsRequired") {
+            self.findings.push(Finding::new(
+                &format!("/extensionsRequired/{}", index),
+                format!(
+                    "{} must not be listed in extensionsRequired. Metadata is not needed to load the asset.",
+                    self.extension
+                ),
+            ));
+        }
+    }
+}
+
+/// Whether an object at the given JSON Pointer may reference a packet.
+fn is_allowed(path: &str) -> bool {
+    if path == "/asset" {
+        return true;
+    }
+
+    let mut segments = path.trim_start_matches('/').split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(category), Some(index), None) => {
+            ALLOWED_CATEGORIES.contains(&category) && index.parse::<usize>().is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(findings: Vec<Finding>) -> Vec<String> {
+        findings.into_iter().map(|f| f.path).collect()
+    }
+
+    #[test]
+    fn accepts_consistent_documents() {
+        let gltf: Gltf = serde_json::from_value(json!({
+            "asset": { "version": "2.0", "extensions": { "KHR_xmp_json_ld": { "packet": 0 } } },
+            "extensionsUsed": ["KHR_xmp_json_ld"],
+            "extensions": { "KHR_xmp_json_ld": { "packets": [{}] } },
+            "nodes": [{ "extensions": { "KHR_xmp_json_ld": { "packet": 0 } } }]
+        }))
+        .unwrap();
+
+        assert!(check_references(&gltf, "KHR_xmp_json_ld").unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_each_problem_with_its_path() {
+        let gltf: Gltf = serde_json::from_value(json!({
+            "asset": { "version": "2.0", "extensions": { "KHR_xmp_json_ld": { "packet": 0 } } },
+            "extensionsRequired": ["KHR_xmp_json_ld"],
+            "extensions": { "KHR_xmp_json_ld": { "packets": [{}] } },
+            "nodes": [{}, { "extensions": { "KHR_xmp_json_ld": { "packet": 3 } } }],
+            "samplers": [{ "extensions": { "KHR_xmp_json_ld": { "packet": 0 } } }],
+            "meshes": [{ "primitives": [{ "extensions": { "KHR_xmp_json_ld": { "packet": 0 } } }] }]
+        }))
+        .unwrap();
+
+        let mut found = paths(check_references(&gltf, "KHR_xmp_json_ld").unwrap());
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                "/extensionsRequired/0",
+                "/extensionsUsed",
+                "/meshes/0/primitives/0/extensions/KHR_xmp_json_ld",
+                "/nodes/1/extensions/KHR_xmp_json_ld/packet",
+                "/samplers/0/extensions/KHR_xmp_json_ld"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_references_without_packets() {
+        let gltf: Gltf = serde_json::from_value(json!({
+            "asset": { "version": "2.0", "extensions": { "KHR_xmp": { "packet": 0 } } },
+            "extensionsUsed": ["KHR_xmp"]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            paths(check_references(&gltf, "KHR_xmp").unwrap()),
+            vec!["/asset/extensions/KHR_xmp/packet"]
+        );
+    }
+}
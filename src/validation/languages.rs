@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::validation::{escape, Finding};
+use crate::xmp::language::{canonicalize, is_well_formed, DEFAULT_LANGUAGE};
+use serde_json::{Map, Value};
+
+/// Checks every `@language` in the packets, in value objects, `rdf:Alt` items and `@context`
+/// defaults alike:
+///  - each tag must be a well-formed BCP 47 language tag,
+///  - an `rdf:Alt` must not have two items with the same language,
+///  - if `require_default` is set, an `rdf:Alt` of language items must have an `x-default` item.
+///
+/// `base_path` is the JSON Pointer of the packet list, used to locate findings.
+pub fn check_languages(packets: &[Value], base_path: &str, require_default: bool) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    for (index, packet) in packets.iter().enumerate() {
+        check_value(packet, &format!("{}/{}", base_path, index), require_default, &mut findings);
+    }
+
+    findings
+}
+
+fn check_value(value: &Value, path: &str, require_default: bool, findings: &mut Vec<Finding>) {
+    match value {
+        Value::Object(object) => {
+            if object.get("@type").and_then(Value::as_str) == Some("rdf:Alt") {
+                check_alt(object, path, require_default, findings);
+            }
+
+            for (key, child) in object {
+                let child_path = format!("{}/{}", path, escape(key));
+                match (key.as_str(), child) {
+                    ("@language", Value::String(tag)) => check_tag(tag, &child_path, findings),
+                    _ => check_value(child, &child_path, require_default, findings),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                check_value(item, &format!("{}/{}", path, index), require_default, findings);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_tag(tag: &str, path: &str, findings: &mut Vec<Finding>) {
+    if is_well_formed(tag) {
+        return;
+    }
+
+    let mut finding = Finding::new(path, format!("\"{}\" is not a well-formed BCP 47 language tag.", tag));
+    // Locale identifiers such as `en_US` use underscores.
+    if let Some(fixed) = canonicalize(&tag.trim().replace('_', "-")) {
+        finding = finding.with_suggestion(format!("\"{}\"", fixed));
+    }
+    findings.push(finding);
+}
+
+fn check_alt(alt: &Map<String, Value>, path: &str, require_default: bool, findings: &mut Vec<Finding>) {
+    let mut languages: Vec<String> = vec![];
+    let mut items: Vec<(u64, &String, &Value)> = alt
+        .iter()
+        .filter_map(|(k, v)| k.strip_prefix("rdf:_").and_then(|n| n.parse().ok()).map(|n| (n, k, v)))
+        .collect();
+    items.sort_by_key(|(n, _, _)| *n);
+
+    for (_, key, item) in items {
+        let language = match item.get("@language").and_then(Value::as_str) {
+            Some(l) => l,
+            None => continue,
+        };
+
+        // Tags are case-insensitive.
+        let normalized = language.to_ascii_lowercase();
+        if languages.contains(&normalized) {
+            findings.push(Finding::new(
+                &format!("{}/{}/@language", path, escape(key)),
+                format!("The language \"{}\" appears more than once in this rdf:Alt.", language),
+            ));
+        } else {
+            languages.push(normalized);
+        }
+    }
+
+    if require_default && !languages.is_empty() && !languages.iter().any(|l| l == DEFAULT_LANGUAGE) {
+        findings.push(Finding::new(
+            path,
+            format!("This rdf:Alt has no {} item.", DEFAULT_LANGUAGE),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(packet: Value, require_default: bool) -> Vec<String> {
+        let mut paths: Vec<String> = check_languages(&[packet], "/packets", require_default)
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn accepts_edge_case_examples() {
+        for example in &[
+            "examples/edge-cases/language-alternatives/language_alternatives.json",
+            "examples/edge-cases/language-attribute/language_attribute.json",
+        ] {
+            let packets = crate::io_helpers::read_json(std::fs::File::open(example).unwrap())
+                .unwrap()
+                .packets;
+            assert!(check_languages(&packets, "/packets", false).is_empty());
+        }
+    }
+
+    #[test]
+    fn reports_malformed_and_duplicate_languages() {
+        let packet = json!({
+            "@context": { "@language": "en_US" },
+            "dc:source": { "@value": "Source", "@language": "english language tag" },
+            "dc:title": {
+                "@type": "rdf:Alt",
+                "rdf:_1": { "@language": "en-US", "@value": "Title" },
+                "rdf:_2": { "@language": "en-us", "@value": "Title" }
+            },
+            "dc:rights": {
+                "@type": "rdf:Alt",
+                "rdf:_1": { "@language": "x-default", "@value": "Rights" }
+            }
+        });
+
+        assert_eq!(
+            paths(packet.clone(), false),
+            vec!["/packets/0/@context/@language", "/packets/0/dc:source/@language", "/packets/0/dc:title/rdf:_2/@language"]
+        );
+        assert_eq!(
+            paths(packet, true),
+            vec![
+                "/packets/0/@context/@language",
+                "/packets/0/dc:source/@language",
+                "/packets/0/dc:title",
+                "/packets/0/dc:title/rdf:_2/@language"
+            ]
+        );
+    }
+}
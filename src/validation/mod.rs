@@ -0,0 +1,49 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+pub mod integrity;
+pub mod languages;
+pub mod packets;
+pub mod values;
+
+/// A single problem found in a glTF document, located by its JSON Pointer.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Finding {
+    pub path: String,
+    pub message: String,
+    /// A corrected value, if one can be guessed.
+    pub suggestion: Option<String>,
+}
+
+impl Finding {
+    pub fn new(path: &str, message: String) -> Finding {
+        Finding {
+            path: path.to_string(),
+            message,
+            suggestion: None,
+        }
+    }
+
+    pub fn with_suggestion(mut self, suggestion: String) -> Finding {
+        self.suggestion = Some(suggestion);
+        self
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " Did you mean {}?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Escapes a JSON Pointer reference token.
+pub fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
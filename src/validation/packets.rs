@@ -0,0 +1,289 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::validation::{escape, Finding};
+use serde_json::{Map, Value};
+
+/// Keywords allowed alongside `@value` in a value object.
+const VALUE_OBJECT_KEYWORDS: [&str; 5] = ["@value", "@language", "@type", "@index", "@direction"];
+
+/// Checks each packet against the `KHR_xmp_json_ld` rules:
+///  - a packet is a JSON object with an inline `@context` object, not a remote context URL,
+///  - every prefix used by a property or `@type` is declared in that `@context`,
+///  - `@graph` is not used,
+///  - `@id`, `@list`, `@set`, `@language` and `@value` are well-formed.
+///
+/// `base_path` is the JSON Pointer of the packet list, used to locate findings.
+pub fn check_packets(packets: &[Value], base_path: &str) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    for (index, packet) in packets.iter().enumerate() {
+        let path = format!("{}/{}", base_path, index);
+        let packet = match packet {
+            Value::Object(p) => p,
+            _ => {
+                findings.push(Finding::new(&path, "A packet must be a JSON object.".to_string()));
+                continue;
+            }
+        };
+
+        let prefixes = check_context(packet.get("@context"), &path, &mut findings);
+        let mut checker = PacketChecker {
+            prefixes,
+            findings: &mut findings,
+        };
+        checker.check_node(packet, &path);
+    }
+
+    findings
+}
+
+/// Checks the `@context` of a packet and returns the prefixes it declares.
+fn check_context(context: Option<&Value>, path: &str, findings: &mut Vec<Finding>) -> Vec<String> {
+    let path = format!("{}/@context", path);
+    let context = match context {
+        Some(Value::Object(c)) => c,
+        Some(Value::String(url)) => {
+            findings.push(Finding::new(
+                &path,
+                format!("The @context must be inline. Remote contexts such as \"{}\" are not allowed.", url),
+            ));
+            return vec![];
+        }
+        Some(_) => {
+            findings.push(Finding::new(&path, "The @context must be a single JSON object.".to_string()));
+            return vec![];
+        }
+        None => {
+            findings.push(Finding::new(&path, "The packet has no inline @context.".to_string()));
+            return vec![];
+        }
+    };
+
+    let mut prefixes = vec![];
+    for (term, definition) in context {
+        if term.starts_with('@') {
+            continue;
+        }
+
+        let is_iri = match definition {
+            Value::String(_) => true,
+            Value::Object(d) => d.get("@id").is_some_and(Value::is_string),
+            _ => false,
+        };
+        if is_iri {
+            prefixes.push(term.clone());
+        } else {
+            findings.push(Finding::new(
+                &format!("{}/{}", path, escape(term)),
+                format!("The prefix \"{}\" must map to an IRI.", term),
+            ));
+        }
+    }
+
+    prefixes
+}
+
+struct PacketChecker<'a> {
+    prefixes: Vec<String>,
+    findings: &'a mut Vec<Finding>,
+}
+
+impl<'a> PacketChecker<'a> {
+    fn push(&mut self, path: &str, message: String) {
+        self.findings.push(Finding::new(path, message));
+    }
+
+    /// Reports a compact IRI (`prefix:name`) whose prefix is not declared. Absolute IRIs such as
+    /// `http://...` are left alone.
+    fn check_prefix(&mut self, path: &str, name: &str) {
+        if let Some((prefix, suffix)) = name.split_once(':') {
+            if !prefix.is_empty() && !suffix.starts_with("//") && !self.prefixes.iter().any(|p| p == prefix) {
+                self.push(
+                    path,
+                    format!("The prefix \"{}\" of \"{}\" is not declared in the packet's @context.", prefix, name),
+                );
+            }
+        }
+    }
+
+    /// Checks a node object: a packet or a structure.
+    fn check_node(&mut self, node: &Map<String, Value>, path: &str) {
+        for (key, value) in node {
+            let value_path = format!("{}/{}", path, escape(key));
+            match key.as_str() {
+                "@context" => {}
+                "@graph" => self.push(&value_path, "@graph is not allowed in packets.".to_string()),
+                "@id" => {
+                    if !value.is_string() {
+                        self.push(&value_path, "@id must be a string.".to_string());
+                    }
+                }
+                "@type" => self.check_type(value, &value_path),
+                keyword if keyword.starts_with('@') => {}
+                property => {
+                    self.check_prefix(&value_path, property);
+                    self.check_value(value, &value_path);
+                }
+            }
+        }
+    }
+
+    fn check_type(&mut self, value: &Value, path: &str) {
+        match value {
+            Value::String(t) => self.check_prefix(path, t),
+            Value::Array(types) if types.iter().all(Value::is_string) => {
+                for (index, t) in types.iter().enumerate() {
+                    self.check_prefix(&format!("{}/{}", path, index), t.as_str().unwrap_or_default());
+                }
+            }
+            _ => self.push(path, "@type must be a string or an array of strings.".to_string()),
+        }
+    }
+
+    /// Checks the value of a property, a list item or a set member.
+    fn check_value(&mut self, value: &Value, path: &str) {
+        let object = match value {
+            Value::Object(o) => o,
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.check_value(item, &format!("{}/{}", path, index));
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        if object.contains_key("@value") {
+            self.check_value_object(object, path);
+        } else if let Some(container) = ["@list", "@set"].iter().find(|k| object.contains_key(**k)) {
+            let items_path = format!("{}/{}", path, container);
+            match &object[*container] {
+                Value::Array(items) => {
+                    for (index, item) in items.iter().enumerate() {
+                        self.check_value(item, &format!("{}/{}", items_path, index));
+                    }
+                }
+                _ => self.push(&items_path, format!("{} must be an array.", container)),
+            }
+
+            // An embedded @context, such as a default @language, applies to the items.
+            if let Some(other) = object.keys().find(|k| *k != container && *k != "@index" && *k != "@context") {
+                self.push(
+                    &format!("{}/{}", path, escape(other)),
+                    format!("An object with {} must not have other properties.", container),
+                );
+            }
+        } else if object.contains_key("@language") {
+            self.push(
+                &format!("{}/@language", path),
+                "@language is only allowed in a value object with @value.".to_string(),
+            );
+        } else {
+            self.check_node(object, path);
+        }
+    }
+
+    fn check_value_object(&mut self, object: &Map<String, Value>, path: &str) {
+        let value = &object["@value"];
+        if value.is_object() || value.is_array() {
+            self.push(&format!("{}/@value", path), "@value must be a string, number, boolean or null.".to_string());
+        }
+
+        for key in object.keys().filter(|k| !VALUE_OBJECT_KEYWORDS.contains(&k.as_str())) {
+            self.push(
+                &format!("{}/{}", path, escape(key)),
+                "A value object may only contain @value, @language, @type, @index and @direction.".to_string(),
+            );
+        }
+
+        if let Some(language) = object.get("@language") {
+            if !language.is_string() {
+                self.push(&format!("{}/@language", path), "@language must be a string.".to_string());
+            } else if !value.is_string() {
+                self.push(&format!("{}/@language", path), "@language requires a string @value.".to_string());
+            }
+
+            if object.contains_key("@type") {
+                self.push(&format!("{}/@type", path), "A value object cannot have both @language and @type.".to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(packets: Vec<Value>) -> Vec<String> {
+        let mut paths: Vec<String> = check_packets(&packets, "/packets").into_iter().map(|f| f.path).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn accepts_sample_packets() {
+        for example in &[
+            "examples/sample.khr_xmp_json_ld.json",
+            "examples/edge-cases/language-alternatives/language_alternatives.json",
+            "examples/edge-cases/language-attribute/language_attribute.json",
+        ] {
+            let packets = crate::io_helpers::read_json(std::fs::File::open(example).unwrap())
+                .unwrap()
+                .packets;
+
+            assert!(check_packets(&packets, "/packets").is_empty(), "{}", example);
+        }
+    }
+
+    #[test]
+    fn reports_context_problems() {
+        assert_eq!(
+            paths(vec![
+                json!("packet"),
+                json!({ "@context": "https://example.com/context.jsonld" }),
+                json!({ "dc:title": "Title" }),
+                json!({ "@context": { "dc": 5 } })
+            ]),
+            vec![
+                "/packets/0",
+                "/packets/1/@context",
+                "/packets/2/@context",
+                "/packets/2/dc:title",
+                "/packets/3/@context/dc"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_malformed_keywords() {
+        let packet = json!({
+            "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+            "@id": 1,
+            "@graph": [],
+            "dc:creator": { "@list": "A", "dc:extra": "B" },
+            "dc:title": { "@value": "Title", "@language": "en", "@type": "dc:Text" },
+            "dc:description": { "@value": ["A"], "dc:extra": 1 },
+            "dc:subject": { "@language": "en" },
+            "dc:relation": { "@id": "https://www.khronos.org/" },
+            "xmp:Rating": 5
+        });
+
+        assert_eq!(
+            paths(vec![packet]),
+            vec![
+                "/packets/0/@graph",
+                "/packets/0/@id",
+                "/packets/0/dc:creator/@list",
+                "/packets/0/dc:creator/dc:extra",
+                "/packets/0/dc:description/@value",
+                "/packets/0/dc:description/dc:extra",
+                "/packets/0/dc:subject/@language",
+                "/packets/0/dc:title/@type",
+                "/packets/0/xmp:Rating"
+            ]
+        );
+    }
+}
@@ -0,0 +1,403 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::validation::{escape, Finding};
+use crate::xmp::namespaces::{property_schema, ValueType};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+
+/// A regular expression that is compiled the first time it is used.
+struct Pattern {
+    source: &'static str,
+    regex: OnceLock<Regex>,
+}
+
+impl Pattern {
+    const fn new(source: &'static str) -> Pattern {
+        Pattern {
+            source,
+            regex: OnceLock::new(),
+        }
+    }
+
+    fn regex(&self) -> &Regex {
+        self.regex
+            .get_or_init(|| Regex::new(self.source).expect("The value patterns are valid regular expressions."))
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        self.regex().is_match(value)
+    }
+}
+
+/// XMP Date: a subset of ISO 8601 where a time, if given, must have a time zone.
+static DATE_PATTERN: Pattern = Pattern::new(
+    r"^\d{4}(?:-(\d{2})(?:-(\d{2})(?:T(\d{2}):(\d{2})(?::(\d{2})(?:\.\d+)?)?(Z|[+-]\d{2}:\d{2})?)?)?)?$",
+);
+static INTEGER_PATTERN: Pattern = Pattern::new(r"^[+-]?\d+$");
+static REAL_PATTERN: Pattern = Pattern::new(r"^[+-]?(?:\d+\.?\d*|\.\d+)$");
+static RATIONAL_PATTERN: Pattern = Pattern::new(r"^[+-]?\d+/(\d+)$");
+static URI_PATTERN: Pattern = Pattern::new(r"^[A-Za-z][A-Za-z0-9+.-]*:\S+$");
+static URL_PATTERN: Pattern = Pattern::new(r"^[A-Za-z][A-Za-z0-9+.-]*://\S+$");
+static MIME_TYPE_PATTERN: Pattern = Pattern::new(r"^([A-Za-z]+)/[A-Za-z0-9][A-Za-z0-9!#$&^_.+-]*(?:\s*;.*)?$");
+
+/// The top-level media types registered with IANA.
+const MEDIA_TYPES: [&str; 10] = [
+    "application", "audio", "example", "font", "image", "message", "model", "multipart", "text", "video",
+];
+
+/// Media types for file extensions that are often given instead of a MIME type.
+const MEDIA_TYPE_EXTENSIONS: [(&str, &str); 7] = [
+    ("gltf", "model/gltf+json"),
+    ("glb", "model/gltf-binary"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("ktx2", "image/ktx2"),
+    ("webp", "image/webp"),
+];
+
+/// Checks the values of standard properties against the XMP value type of their schema:
+/// Date, Integer, Real, Rational, Boolean, URI, URL and MIMEType. Values of other types, and
+/// properties without a schema, are not checked.
+///
+/// `base_path` is the JSON Pointer of the packet list, used to locate findings.
+pub fn check_values(packets: &[Value], base_path: &str) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    for (index, packet) in packets.iter().enumerate() {
+        if let Value::Object(packet) = packet {
+            check_node(packet, &format!("{}/{}", base_path, index), &mut findings);
+        }
+    }
+
+    findings
+}
+
+fn check_node(node: &Map<String, Value>, path: &str, findings: &mut Vec<Finding>) {
+    for (property, value) in node.iter().filter(|(k, _)| !k.starts_with('@')) {
+        let value_type = property_schema(property).map(|schema| schema.value_type);
+        check_items(property, value_type, value, &format!("{}/{}", path, escape(property)), findings);
+    }
+}
+
+/// Checks a property value, looking through `@list`, `@set`, `rdf:Alt` and value objects, and
+/// checks the properties of structures.
+fn check_items(
+    property: &str,
+    value_type: Option<ValueType>,
+    value: &Value,
+    path: &str,
+    findings: &mut Vec<Finding>,
+) {
+    let object = match value {
+        Value::Object(o) => o,
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                check_items(property, value_type, item, &format!("{}/{}", path, index), findings);
+            }
+            return;
+        }
+        scalar => {
+            if let Some(value_type) = value_type {
+                check_value(property, value_type, scalar, path, findings);
+            }
+            return;
+        }
+    };
+
+    if let Some(keyword) = ["@list", "@set", "@value"].iter().find(|k| object.contains_key(**k)) {
+        let item_path = format!("{}/{}", path, keyword);
+        return check_items(property, value_type, &object[*keyword], &item_path, findings);
+    }
+
+    if object.get("@type").and_then(Value::as_str) == Some("rdf:Alt") {
+        for (key, item) in object.iter().filter(|(k, _)| k.starts_with("rdf:_")) {
+            check_items(property, value_type, item, &format!("{}/{}", path, escape(key)), findings);
+        }
+    } else if let (Some(id), 1) = (object.get("@id"), object.len()) {
+        // A reference to a resource, which URI properties may use instead of a string.
+        if let Some(value_type @ (ValueType::Uri | ValueType::Url)) = value_type {
+            check_value(property, value_type, id, &format!("{}/@id", path), findings);
+        }
+    } else {
+        check_node(object, path, findings);
+    }
+}
+
+fn check_value(property: &str, value_type: ValueType, value: &Value, path: &str, findings: &mut Vec<Finding>) {
+    let problem = match value_type {
+        ValueType::Date => check_date(value),
+        ValueType::Integer => check_integer(value),
+        ValueType::Real => check_real(value),
+        ValueType::Rational => check_rational(value),
+        ValueType::Boolean => check_boolean(value),
+        ValueType::Uri | ValueType::Url => check_uri(value, value_type),
+        ValueType::MimeType => check_mime_type(value),
+        _ => None,
+    };
+
+    if let Some((reason, suggestion)) = problem {
+        let mut finding = Finding::new(
+            path,
+            format!("{} is not a valid {} for {}. {}", value, value_type, property, reason),
+        );
+        if let Some(suggestion) = suggestion {
+            finding = finding.with_suggestion(suggestion);
+        }
+        findings.push(finding);
+    }
+}
+
+/// A reason the value is invalid, and a corrected value if one can be guessed.
+type Problem = Option<(String, Option<String>)>;
+
+fn check_date(value: &Value) -> Problem {
+    let date = match value.as_str() {
+        Some(d) => d,
+        None => return Some(("An XMP Date must be a string.".to_string(), None)),
+    };
+
+    match date_problem(date) {
+        Some(reason) => {
+            // Common mistakes: other separators, a space before the time and no time zone.
+            let mut fixed = date.replace('/', "-").replacen(' ', "T", 1);
+            if fixed.len() > 10 && date_problem(&fixed).is_some() && date_problem(&format!("{}Z", fixed)).is_none() {
+                fixed.push('Z');
+            }
+            let suggestion = if date_problem(&fixed).is_none() { Some(format!("\"{}\"", fixed)) } else { None };
+            Some((reason, suggestion))
+        }
+        None => None,
+    }
+}
+
+fn date_problem(date: &str) -> Option<String> {
+    let captures = match DATE_PATTERN.regex().captures(date) {
+        Some(c) => c,
+        None => {
+            return Some("Use YYYY, YYYY-MM, YYYY-MM-DD or YYYY-MM-DDThh:mm[:ss[.s]] with a time zone.".to_string())
+        }
+    };
+
+    let field = |index: usize| captures.get(index).and_then(|m| m.as_str().parse::<u32>().ok());
+    let in_range = [(1, 1, 12), (2, 1, 31), (3, 0, 23), (4, 0, 59), (5, 0, 59)]
+        .iter()
+        .all(|(index, min, max)| field(*index).is_none_or(|v| v >= *min && v <= *max));
+    if !in_range {
+        return Some("A date or time field is out of range.".to_string());
+    }
+
+    // The pattern guarantees four leading digits.
+    let year = date[..4].parse::<u32>().unwrap_or_default();
+    if let (Some(month), Some(day)) = (field(1), field(2)) {
+        if day > days_in_month(year, month) {
+            return Some(format!("Month {} of {} has {} days.", month, year, days_in_month(year, month)));
+        }
+    }
+
+    if captures.get(3).is_some() && captures.get(6).is_none() {
+        return Some("A time must have a time zone, e.g. Z or +01:00.".to_string());
+    }
+
+    None
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn check_integer(value: &Value) -> Problem {
+    let reason = "An XMP Integer is a whole number.".to_string();
+    match value {
+        Value::Number(n) if n.is_i64() || n.is_u64() => None,
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            Some((reason, if n.fract() == 0.0 { Some((n as i64).to_string()) } else { None }))
+        }
+        Value::String(s) if INTEGER_PATTERN.is_match(s) => None,
+        Value::String(s) => {
+            let suggestion = s.parse::<f64>().ok().filter(|n| n.fract() == 0.0).map(|n| (n as i64).to_string());
+            Some((reason, suggestion))
+        }
+        _ => Some((reason, None)),
+    }
+}
+
+fn check_real(value: &Value) -> Problem {
+    let reason = "An XMP Real is a decimal number with a period as the separator.".to_string();
+    match value {
+        Value::Number(_) => None,
+        Value::String(s) if REAL_PATTERN.is_match(s) => None,
+        Value::String(s) => {
+            let fixed = s.trim().replace(',', ".");
+            Some((reason, if REAL_PATTERN.is_match(&fixed) { Some(format!("\"{}\"", fixed)) } else { None }))
+        }
+        _ => Some((reason, None)),
+    }
+}
+
+fn check_rational(value: &Value) -> Problem {
+    let reason = "An XMP Rational is a string of the form \"numerator/denominator\".".to_string();
+    match value {
+        Value::String(s) => match RATIONAL_PATTERN.regex().captures(s) {
+            Some(c) if c[1].chars().all(|d| d == '0') => {
+                Some(("The denominator of an XMP Rational must not be zero.".to_string(), None))
+            }
+            Some(_) => None,
+            None => {
+                let fixed = s.replace(':', "/");
+                Some((reason, if RATIONAL_PATTERN.is_match(&fixed) { Some(format!("\"{}\"", fixed)) } else { None }))
+            }
+        },
+        Value::Number(n) if n.is_i64() || n.is_u64() => Some((reason, Some(format!("\"{}/1\"", n)))),
+        _ => Some((reason, None)),
+    }
+}
+
+fn check_boolean(value: &Value) -> Problem {
+    let reason = "An XMP Boolean is the string \"True\" or \"False\".".to_string();
+    let suggestion = match value {
+        Value::String(s) if s == "True" || s == "False" => return None,
+        Value::Bool(b) => Some(*b),
+        Value::String(s) if s.eq_ignore_ascii_case("true") => Some(true),
+        Value::String(s) if s.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    };
+    Some((reason, suggestion.map(|b| if b { "\"True\"" } else { "\"False\"" }.to_string())))
+}
+
+fn check_uri(value: &Value, value_type: ValueType) -> Problem {
+    let (pattern, reason) = if value_type == ValueType::Url {
+        (&URL_PATTERN, "An XMP URL is an absolute URL, e.g. https://www.khronos.org/.")
+    } else {
+        (&URI_PATTERN, "An XMP URI is an absolute URI with a scheme, e.g. https: or urn:.")
+    };
+    let reason = reason.to_string();
+
+    let uri = match value.as_str() {
+        Some(u) if pattern.is_match(u) => return None,
+        Some(u) => u.trim(),
+        None => return Some((reason, None)),
+    };
+
+    let mut fixed = uri.replace(' ', "%20");
+    if !URI_PATTERN.is_match(&fixed) && fixed.starts_with("www.") {
+        fixed = format!("https://{}", fixed);
+    }
+    Some((reason, if pattern.is_match(&fixed) { Some(format!("\"{}\"", fixed)) } else { None }))
+}
+
+fn check_mime_type(value: &Value) -> Problem {
+    let reason = "An XMP MIMEType is a registered media type, e.g. model/gltf-binary.".to_string();
+    let mime_type = match value.as_str() {
+        Some(m) => m,
+        None => return Some((reason, None)),
+    };
+
+    let media_type = MIME_TYPE_PATTERN.regex().captures(mime_type).map(|c| c[1].to_ascii_lowercase());
+    match media_type {
+        Some(t) if MEDIA_TYPES.contains(&t.as_str()) => {
+            if mime_type.chars().any(|c| c.is_ascii_uppercase()) {
+                // Media types are case-insensitive, but lower case is conventional.
+                return Some((
+                    "Media types are written in lower case.".to_string(),
+                    Some(format!("\"{}\"", mime_type.to_ascii_lowercase())),
+                ));
+            }
+            None
+        }
+        _ => {
+            let extension = mime_type.trim_start_matches('.').to_ascii_lowercase();
+            let suggestion = MEDIA_TYPE_EXTENSIONS
+                .iter()
+                .find(|(e, _)| *e == extension)
+                .map(|(_, t)| format!("\"{}\"", t));
+            Some((reason, suggestion))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn findings(packet: Value) -> Vec<(String, Option<String>)> {
+        let mut found: Vec<(String, Option<String>)> = check_values(&[packet], "/packets")
+            .into_iter()
+            .map(|f| (f.path, f.suggestion))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn accepts_sample_values() {
+        let packets = crate::io_helpers::read_json(std::fs::File::open("examples/sample.khr_xmp_json_ld.json").unwrap())
+            .unwrap()
+            .packets;
+
+        assert!(check_values(&packets, "/packets").is_empty());
+    }
+
+    #[test]
+    fn accepts_valid_values() {
+        let packet = json!({
+            "dc:date": { "@list": ["1997", "1997-07", "1997-07-16", "1997-07-16T19:20Z", "1997-07-16T19:20:30.45-05:00", "2000-02-29", "2024-04-30"] },
+            "dc:format": "model/gltf+json",
+            "xmp:Rating": "4.5",
+            "xmpRights:Marked": "True",
+            "xmpRights:WebStatement": { "@id": "https://www.khronos.org/" },
+            "exif:ExposureTime": "1/250",
+            "exif:PixelXDimension": 1024,
+            "xmpMM:DocumentID": "uuid:6f5b1e8c",
+            "dc:title": "Any text"
+        });
+
+        assert!(findings(packet).is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_values_with_suggestions() {
+        let packet = json!({
+            "dc:date": { "@list": ["1997/07/16", "1997-07-16T19:20:30", "1997-13-01", 1997, "2021-02-31", "1900-02-29", "2021-04-31"] },
+            "dc:format": "GLB",
+            "xmp:Rating": "4,5",
+            "xmpRights:Marked": true,
+            "xmpRights:WebStatement": "www.khronos.org",
+            "exif:ExposureTime": "1:250",
+            "exif:FNumber": "4/0",
+            "exif:PixelXDimension": "1024.0",
+            "xmpMM:DerivedFrom": { "xmpMM:DocumentID": "not a uri" }
+        });
+
+        assert_eq!(
+            findings(packet),
+            vec![
+                ("/packets/0/dc:date/@list/0".to_string(), Some("\"1997-07-16\"".to_string())),
+                ("/packets/0/dc:date/@list/1".to_string(), Some("\"1997-07-16T19:20:30Z\"".to_string())),
+                ("/packets/0/dc:date/@list/2".to_string(), None),
+                ("/packets/0/dc:date/@list/3".to_string(), None),
+                ("/packets/0/dc:date/@list/4".to_string(), None),
+                ("/packets/0/dc:date/@list/5".to_string(), None),
+                ("/packets/0/dc:date/@list/6".to_string(), None),
+                ("/packets/0/dc:format".to_string(), Some("\"model/gltf-binary\"".to_string())),
+                ("/packets/0/exif:ExposureTime".to_string(), Some("\"1/250\"".to_string())),
+                ("/packets/0/exif:FNumber".to_string(), None),
+                ("/packets/0/exif:PixelXDimension".to_string(), Some("1024".to_string())),
+                ("/packets/0/xmp:Rating".to_string(), Some("\"4.5\"".to_string())),
+                ("/packets/0/xmpMM:DerivedFrom/xmpMM:DocumentID".to_string(), None),
+                ("/packets/0/xmpRights:Marked".to_string(), Some("\"True\"".to_string())),
+                ("/packets/0/xmpRights:WebStatement".to_string(), Some("\"https://www.khronos.org\"".to_string())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::validation::escape;
+use crate::xmp::alternatives::{alternatives, is_alt, parse_language, same_language, set_alternative, LanguageChange};
+use crate::xmp::{escape_xml, XML_NAMESPACE};
+use roxmltree::{Document, Node};
+use serde_json::Value;
+
+const XLIFF_NAMESPACE: &str = "urn:oasis:names:tc:xliff:document:1.2";
+
+/// A language alternative to translate.
+#[derive(PartialEq, Debug)]
+pub struct TranslationUnit {
+    /// The packet index and the JSON Pointer of the `rdf:Alt` within the packet, e.g.
+    /// `0/dc:title`.
+    pub id: String,
+    /// The property holding the alternative.
+    pub property: String,
+    pub source: String,
+    pub target: Option<String>,
+}
+
+/// Collects every language alternative of the packets that has an entry in `source_language`,
+/// along with its entry in `target_language`, if any.
+pub fn collect_units(packets: &[Value], source_language: &str, target_language: Option<&str>) -> Vec<TranslationUnit> {
+    let mut units = vec![];
+    for (index, packet) in packets.iter().enumerate() {
+        collect(packet, &index.to_string(), None, source_language, target_language, &mut units);
+    }
+    units
+}
+
+fn collect(
+    value: &Value,
+    id: &str,
+    property: Option<&str>,
+    source_language: &str,
+    target_language: Option<&str>,
+    units: &mut Vec<TranslationUnit>,
+) {
+    match value {
+        Value::Object(object) if is_alt(object) => {
+            let items = alternatives(object);
+            let text = |language: &str| {
+                items
+                    .iter()
+                    .find(|i| same_language(i, language))
+                    .and_then(|i| i.get("@value"))
+                    .and_then(Value::as_str)
+                    .map(String::from)
+            };
+
+            if let (Some(source), Some(property)) = (text(source_language), property) {
+                units.push(TranslationUnit {
+                    id: id.to_string(),
+                    property: property.to_string(),
+                    source,
+                    target: target_language.and_then(text),
+                });
+            }
+        }
+        Value::Object(object) => {
+            for (key, child) in object.iter().filter(|(k, _)| *k != "@context") {
+                // Keywords such as @list keep the property they belong to.
+                let child_property = if key.starts_with('@') { property } else { Some(key.as_str()) };
+                let child_id = format!("{}/{}", id, escape(key));
+                collect(child, &child_id, child_property, source_language, target_language, units);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let item_id = format!("{}/{}", id, index);
+                collect(item, &item_id, property, source_language, target_language, units);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Serializes translation units into an XLIFF 1.2 document with a single `file` named
+/// `original`.
+pub fn write_xliff(
+    units: &[TranslationUnit],
+    original: &str,
+    source_language: &str,
+    target_language: Option<&str>,
+) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!("<xliff version=\"1.2\" xmlns=\"{}\">\n", XLIFF_NAMESPACE));
+    out.push_str(&format!(
+        "  <file original=\"{}\" source-language=\"{}\"",
+        escape_xml(original),
+        escape_xml(source_language)
+    ));
+    if let Some(target_language) = target_language {
+        out.push_str(&format!(" target-language=\"{}\"", escape_xml(target_language)));
+    }
+    out.push_str(" datatype=\"plaintext\">\n");
+    out.push_str("    <body>\n");
+
+    for unit in units {
+        out.push_str(&format!(
+            "      <trans-unit id=\"{}\" resname=\"{}\">\n",
+            escape_xml(&unit.id),
+            escape_xml(&unit.property)
+        ));
+        out.push_str(&format!("        <source>{}</source>\n", escape_xml(&unit.source)));
+        if let Some(target) = &unit.target {
+            out.push_str(&format!("        <target>{}</target>\n", escape_xml(target)));
+        }
+        out.push_str("      </trans-unit>\n");
+    }
+
+    out.push_str("    </body>\n");
+    out.push_str("  </file>\n");
+    out.push_str("</xliff>\n");
+    out
+}
+
+/// A translated language alternative read from an XLIFF document.
+#[derive(PartialEq, Debug)]
+pub struct Translation {
+    pub id: String,
+    pub language: String,
+    pub text: String,
+}
+
+fn is_element(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+/// Reads the translated units of an XLIFF 1.2 document. The language of a target is its
+/// `xml:lang`, or else the `target-language` of its file. Units without a target are skipped.
+pub fn parse_xliff(text: &str) -> Result<Vec<Translation>, String> {
+    let document = Document::parse(text.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("Unable to parse XLIFF input: {}", e))?;
+    let root = document.root_element();
+    if !is_element(&root, "xliff") {
+        return Err("The XLIFF input has no xliff root element.".to_string());
+    }
+
+    let mut translations = vec![];
+    for file in root.children().filter(|n| is_element(n, "file")) {
+        let file_language = file.attribute("target-language");
+
+        for unit in file.descendants().filter(|n| is_element(n, "trans-unit")) {
+            let id = unit
+                .attribute("id")
+                .ok_or_else(|| "A trans-unit in the XLIFF input has no id.".to_string())?;
+            let target = match unit.children().find(|n| is_element(n, "target")) {
+                Some(t) => t,
+                None => continue,
+            };
+            let text: String = target.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect();
+            if text.is_empty() {
+                continue;
+            }
+
+            let language = target
+                .attribute((XML_NAMESPACE, "lang"))
+                .or(file_language)
+                .ok_or_else(|| format!("The target of trans-unit \"{}\" has no language. Set target-language on its file.", id))?;
+
+            translations.push(Translation {
+                id: id.to_string(),
+                language: parse_language(language)?,
+                text,
+            });
+        }
+    }
+
+    Ok(translations)
+}
+
+/// Writes translations into the language alternatives they were exported from, adding or
+/// replacing the entry in their language.
+pub fn apply_translations(packets: &mut [Value], translations: &[Translation]) -> Result<Vec<LanguageChange>, String> {
+    let mut changes = vec![];
+
+    for translation in translations {
+        let not_found = || {
+            format!(
+                "Trans-unit \"{}\" does not match a language alternative in the metadata.",
+                translation.id
+            )
+        };
+
+        let (packet, pointer) = match translation.id.split_once('/') {
+            Some((packet, pointer)) => (packet, format!("/{}", pointer)),
+            None => return Err(not_found()),
+        };
+        let alt = packet
+            .parse::<usize>()
+            .ok()
+            .and_then(|p| packets.get_mut(p))
+            .and_then(|p| p.pointer_mut(&pointer))
+            .and_then(Value::as_object_mut)
+            .filter(|o| is_alt(o))
+            .ok_or_else(not_found)?;
+
+        changes.push(set_alternative(alt, &translation.language, &translation.text));
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn packets() -> Vec<Value> {
+        vec![
+            json!({
+                "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+                "dc:title": {
+                    "@type": "rdf:Alt",
+                    "rdf:_1": { "@language": "x-default", "@value": "Fish & Chips" },
+                    "rdf:_2": { "@language": "it-IT", "@value": "Pesce" }
+                },
+                "dc:source": "Not an alternative"
+            }),
+            json!({
+                "plus:Licensor": { "@list": [{
+                    "dc:rights": { "@type": "rdf:Alt", "rdf:_1": { "@language": "x-default", "@value": "Rights" } }
+                }] },
+                "dc:description": { "@type": "rdf:Alt", "rdf:_1": { "@language": "en-US", "@value": "English" } }
+            }),
+        ]
+    }
+
+    #[test]
+    fn collects_units() {
+        let units = collect_units(&packets(), "x-default", Some("it-IT"));
+        let ids: Vec<&str> = units.iter().map(|u| u.id.as_str()).collect();
+        assert_eq!(ids, vec!["0/dc:title", "1/plus:Licensor/@list/0/dc:rights"]);
+        assert_eq!(units[0].target, Some("Pesce".to_string()));
+        assert_eq!(units[1].property, "dc:rights");
+
+        let units = collect_units(&packets(), "en-us", None);
+        assert_eq!(units[0].id, "1/dc:description");
+    }
+
+    #[test]
+    fn round_trips_translations() {
+        let units = collect_units(&packets(), "x-default", None);
+        let xliff = write_xliff(&units, "model.glb", "x-default", None)
+            .replace("<source>Fish &amp; Chips</source>", "<source>Fish &amp; Chips</source><target xml:lang=\"fr-fr\">Poisson &amp; frites</target>")
+            .replace("<source>Rights</source>", "<source>Rights</source><target xml:lang=\"it-IT\">Diritti</target>");
+
+        let translations = parse_xliff(&xliff).unwrap();
+        assert_eq!(
+            translations[0],
+            Translation {
+                id: "0/dc:title".to_string(),
+                language: "fr-FR".to_string(),
+                text: "Poisson & frites".to_string()
+            }
+        );
+
+        let mut packets = packets();
+        apply_translations(&mut packets, &translations).unwrap();
+        assert_eq!(packets[0]["dc:title"]["rdf:_3"], json!({ "@language": "fr-FR", "@value": "Poisson & frites" }));
+        assert_eq!(
+            packets[1]["plus:Licensor"]["@list"][0]["dc:rights"]["rdf:_2"],
+            json!({ "@language": "it-IT", "@value": "Diritti" })
+        );
+
+        let unknown = vec![Translation {
+            id: "0/dc:source".to_string(),
+            language: "fr-FR".to_string(),
+            text: "Source".to_string(),
+        }];
+        assert!(apply_translations(&mut packets, &unknown).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::xmp::language::{canonicalize, DEFAULT_LANGUAGE};
+use serde_json::{json, Map, Value};
+
+/// Whether an object is an `rdf:Alt`.
+pub fn is_alt(object: &Map<String, Value>) -> bool {
+    object.get("@type").and_then(Value::as_str) == Some("rdf:Alt")
+}
+
+/// The `rdf:_N` items of an `rdf:Alt` object, in order.
+pub fn alternatives(alt: &Map<String, Value>) -> Vec<&Value> {
+    let mut items: Vec<(u64, &Value)> = alt
+        .iter()
+        .filter_map(|(k, v)| k.strip_prefix("rdf:_").and_then(|n| n.parse().ok()).map(|n| (n, v)))
+        .collect();
+    items.sort_by_key(|(n, _)| *n);
+    items.into_iter().map(|(_, v)| v).collect()
+}
+
+/// Replaces the items of an `rdf:Alt` object, numbering them `rdf:_1` to `rdf:_N`.
+pub fn set_alternatives(alt: &mut Map<String, Value>, items: Vec<Value>) {
+    alt.retain(|k, _| !k.starts_with("rdf:_"));
+    for (index, item) in items.into_iter().enumerate() {
+        alt.insert(format!("rdf:_{}", index + 1), item);
+    }
+}
+
+/// How `set_language` changed a language alternative.
+#[derive(PartialEq, Debug)]
+pub enum LanguageChange {
+    Added,
+    Replaced,
+}
+
+pub fn language_of(item: &Value) -> Option<&str> {
+    item.get("@language").and_then(Value::as_str)
+}
+
+pub fn same_language(item: &Value, language: &str) -> bool {
+    language_of(item).is_some_and(|l| l.eq_ignore_ascii_case(language))
+}
+
+/// Checks a language tag given on the command line and returns it in canonical case.
+pub fn parse_language(language: &str) -> Result<String, String> {
+    canonicalize(language).ok_or_else(|| format!("\"{}\" is not a well-formed BCP 47 language tag.", language))
+}
+
+/// Returns the language alternatives of a property in a packet.
+fn alt_mut<'a>(packet: &'a mut Value, property: &str) -> Result<&'a mut Map<String, Value>, String> {
+    let packet = packet
+        .as_object_mut()
+        .ok_or_else(|| "A packet must be a JSON object.".to_string())?;
+
+    match packet.get_mut(property) {
+        Some(Value::Object(alt)) if is_alt(alt) => Ok(alt),
+        Some(_) => Err(format!(
+            "{} is not a language alternative (rdf:Alt). Use --normalize to convert it.",
+            property
+        )),
+        None => Err(format!("The packet has no {} property.", property)),
+    }
+}
+
+/// Sets the value of a property in one language, replacing an existing entry for that language
+/// or adding a new one at the end.
+pub fn set_language(packet: &mut Value, property: &str, language: &str, value: &str) -> Result<LanguageChange, String> {
+    let language = parse_language(language)?;
+    if let Some(packet) = packet.as_object_mut() {
+        packet.entry(property).or_insert_with(|| json!({ "@type": "rdf:Alt" }));
+    }
+    let alt = alt_mut(packet, property)?;
+
+    Ok(set_alternative(alt, &language, value))
+}
+
+/// Sets the entry of an `rdf:Alt` object in one language, replacing an existing entry for that
+/// language or adding a new one at the end.
+pub fn set_alternative(alt: &mut Map<String, Value>, language: &str, value: &str) -> LanguageChange {
+    let mut items: Vec<Value> = alternatives(alt).into_iter().cloned().collect();
+    let item = json!({ "@language": language, "@value": value });
+    let change = match items.iter_mut().find(|i| same_language(i, language)) {
+        Some(existing) => {
+            *existing = item;
+            LanguageChange::Replaced
+        }
+        None => {
+            items.push(item);
+            LanguageChange::Added
+        }
+    };
+    set_alternatives(alt, items);
+
+    change
+}
+
+/// Removes the entry of a property in one language. The property is removed with its last entry.
+/// Returns whether there was such an entry.
+pub fn remove_language(packet: &mut Value, property: &str, language: &str) -> Result<bool, String> {
+    let language = parse_language(language)?;
+    if packet.get(property).is_none() {
+        return Ok(false);
+    }
+    let alt = alt_mut(packet, property)?;
+
+    let items: Vec<Value> = alternatives(alt).into_iter().cloned().collect();
+    let count = items.len();
+    let items: Vec<Value> = items.into_iter().filter(|i| !same_language(i, &language)).collect();
+    let removed = items.len() != count;
+
+    if items.is_empty() {
+        if let Some(packet) = packet.as_object_mut() {
+            packet.remove(property);
+        }
+    } else {
+        set_alternatives(alt, items);
+    }
+
+    Ok(removed)
+}
+
+/// Makes the entry of a property in one language the default: its value is copied to the
+/// `x-default` entry, which is moved first as XMP recommends.
+pub fn set_default_language(packet: &mut Value, property: &str, language: &str) -> Result<(), String> {
+    let language = parse_language(language)?;
+    let alt = alt_mut(packet, property)?;
+
+    let items: Vec<Value> = alternatives(alt).into_iter().cloned().collect();
+    let value = items
+        .iter()
+        .find(|i| same_language(i, &language))
+        .and_then(|i| i.get("@value"))
+        .cloned()
+        .ok_or_else(|| format!("{} has no \"{}\" entry to use as the default.", property, language))?;
+
+    let mut reordered = vec![json!({ "@language": DEFAULT_LANGUAGE, "@value": value })];
+    reordered.extend(items.into_iter().filter(|i| !same_language(i, DEFAULT_LANGUAGE)));
+    set_alternatives(alt, reordered);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn title() -> Value {
+        json!({
+            "dc:title": {
+                "@type": "rdf:Alt",
+                "rdf:_1": { "@language": "x-default", "@value": "My Model" },
+                "rdf:_2": { "@language": "en-US", "@value": "My Model" },
+                "rdf:_3": { "@language": "fr-FR", "@value": "Mon modèle" }
+            }
+        })
+    }
+
+    #[test]
+    fn sets_and_removes_languages() {
+        let mut packet = title();
+
+        assert_eq!(set_language(&mut packet, "dc:title", "it-it", "Mio Modello").unwrap(), LanguageChange::Added);
+        assert_eq!(set_language(&mut packet, "dc:title", "en-US", "Model").unwrap(), LanguageChange::Replaced);
+        assert!(remove_language(&mut packet, "dc:title", "fr-FR").unwrap());
+        assert!(!remove_language(&mut packet, "dc:title", "de-DE").unwrap());
+        assert_eq!(
+            packet["dc:title"],
+            json!({
+                "@type": "rdf:Alt",
+                "rdf:_1": { "@language": "x-default", "@value": "My Model" },
+                "rdf:_2": { "@language": "en-US", "@value": "Model" },
+                "rdf:_3": { "@language": "it-IT", "@value": "Mio Modello" }
+            })
+        );
+
+        set_language(&mut packet, "dc:rights", "en", "CC-BY").unwrap();
+        assert_eq!(packet["dc:rights"]["rdf:_1"], json!({ "@language": "en", "@value": "CC-BY" }));
+        remove_language(&mut packet, "dc:rights", "en").unwrap();
+        assert!(packet.get("dc:rights").is_none());
+
+        assert!(set_language(&mut packet, "dc:title", "en_US", "Model").is_err());
+        packet["dc:source"] = json!("Source");
+        assert!(set_language(&mut packet, "dc:source", "en", "Source").is_err());
+    }
+
+    #[test]
+    fn sets_default_language() {
+        let mut packet = title();
+
+        set_default_language(&mut packet, "dc:title", "fr-fr").unwrap();
+        assert_eq!(
+            packet["dc:title"],
+            json!({
+                "@type": "rdf:Alt",
+                "rdf:_1": { "@language": "x-default", "@value": "Mon modèle" },
+                "rdf:_2": { "@language": "en-US", "@value": "My Model" },
+                "rdf:_3": { "@language": "fr-FR", "@value": "Mon modèle" }
+            })
+        );
+        assert!(set_default_language(&mut packet, "dc:title", "de-DE").is_err());
+    }
+}
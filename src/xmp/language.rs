@@ -0,0 +1,226 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::Value;
+
+/// The language of the default item of a Lang Alt.
+pub const DEFAULT_LANGUAGE: &str = "x-default";
+
+/// The irregular grandfathered tags of RFC 5646, which do not follow the tag grammar, in their
+/// registered case.
+const IRREGULAR_TAGS: [&str; 17] = [
+    "en-GB-oed", "i-ami", "i-bnn", "i-default", "i-enochian", "i-hak", "i-klingon", "i-lux", "i-mingo",
+    "i-navajo", "i-pwn", "i-tao", "i-tay", "i-tsu", "sgn-BE-FR", "sgn-BE-NL", "sgn-CH-DE",
+];
+
+/// The kind of a subtag, which decides its canonical case.
+#[derive(Clone, Copy, PartialEq)]
+enum Subtag {
+    Language,
+    ExtendedLanguage,
+    Script,
+    Region,
+    Variant,
+    Extension,
+    PrivateUse,
+}
+
+fn is_alpha(s: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn is_alphanumeric(s: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn is_variant(s: &str) -> bool {
+    is_alphanumeric(s, 5, 8) || (s.len() == 4 && s.starts_with(|c: char| c.is_ascii_digit()) && is_alphanumeric(s, 4, 4))
+}
+
+struct Parser<'a, 'b> {
+    rest: &'b [&'a str],
+    parsed: Vec<(&'a str, Subtag)>,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    /// Consumes the next subtag as `kind` if `accepts` allows it.
+    fn take(&mut self, kind: Subtag, accepts: impl Fn(&str) -> bool) -> bool {
+        match self.rest.first() {
+            Some(s) if accepts(s) => {
+                self.parsed.push((s, kind));
+                self.rest = &self.rest[1..];
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn next_is_singleton(&self, private_use: bool) -> bool {
+        self.rest
+            .first()
+            .is_some_and(|s| s.len() == 1 && s.eq_ignore_ascii_case("x") == private_use)
+    }
+
+    /// Consumes a singleton and the subtags that follow it, failing if there are none.
+    fn take_sequence(&mut self, kind: Subtag, min: usize) -> bool {
+        self.take(kind, |_| true);
+        if !self.take(kind, |s| is_alphanumeric(s, min, 8)) {
+            return false;
+        }
+        while self.take(kind, |s| is_alphanumeric(s, min, 8)) {}
+        true
+    }
+}
+
+/// Splits a tag into its subtags and their kinds, following the `langtag` and `privateuse`
+/// productions of RFC 5646. Returns `None` if the tag is not well-formed.
+fn parse(tag: &str) -> Option<Vec<(&str, Subtag)>> {
+    let subtags: Vec<&str> = tag.split('-').collect();
+    let mut parser = Parser {
+        rest: &subtags,
+        parsed: vec![],
+    };
+
+    // A tag may also consist only of private use subtags, such as `x-default`.
+    if parser.take(Subtag::Language, |s| is_alpha(s, 2, 8)) {
+        if parser.parsed[0].0.len() <= 3 {
+            for _ in 0..3 {
+                if !parser.take(Subtag::ExtendedLanguage, |s| is_alpha(s, 3, 3)) {
+                    break;
+                }
+            }
+        }
+        parser.take(Subtag::Script, |s| is_alpha(s, 4, 4));
+        parser.take(Subtag::Region, |s| {
+            is_alpha(s, 2, 2) || (s.len() == 3 && s.chars().all(|c| c.is_ascii_digit()))
+        });
+        while parser.take(Subtag::Variant, is_variant) {}
+
+        while parser.next_is_singleton(false) {
+            if !parser.take_sequence(Subtag::Extension, 2) {
+                return None;
+            }
+        }
+    } else if !parser.next_is_singleton(true) {
+        return None;
+    }
+
+    if parser.next_is_singleton(true) && !parser.take_sequence(Subtag::PrivateUse, 1) {
+        return None;
+    }
+
+    if parser.rest.is_empty() {
+        Some(parser.parsed)
+    } else {
+        None
+    }
+}
+
+fn irregular_tag(tag: &str) -> Option<&'static str> {
+    IRREGULAR_TAGS.iter().find(|t| t.eq_ignore_ascii_case(tag)).cloned()
+}
+
+/// Whether a tag is a well-formed BCP 47 language tag.
+pub fn is_well_formed(tag: &str) -> bool {
+    irregular_tag(tag).is_some() || parse(tag).is_some()
+}
+
+/// Returns a well-formed tag in the case recommended by RFC 5646: lower case, except for title
+/// case scripts and upper case regions, e.g. `zh-Hant-TW`. Returns `None` if the tag is not
+/// well-formed.
+pub fn canonicalize(tag: &str) -> Option<String> {
+    if let Some(irregular) = irregular_tag(tag) {
+        return Some(irregular.to_string());
+    }
+
+    let subtags: Vec<String> = parse(tag)?
+        .into_iter()
+        .map(|(subtag, kind)| match kind {
+            Subtag::Script => {
+                let (first, rest) = subtag.split_at(1);
+                first.to_ascii_uppercase() + &rest.to_ascii_lowercase()
+            }
+            Subtag::Region => subtag.to_ascii_uppercase(),
+            _ => subtag.to_ascii_lowercase(),
+        })
+        .collect();
+    Some(subtags.join("-"))
+}
+
+/// Canonicalizes the case of every well-formed `@language` in a packet, including a default
+/// language set in a `@context`. Returns each tag that changed along with its canonical form.
+pub fn canonicalize_languages(value: &mut Value) -> Vec<(String, String)> {
+    let mut changed = vec![];
+    canonicalize_value(value, &mut changed);
+    changed
+}
+
+fn canonicalize_value(value: &mut Value, changed: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object.iter_mut() {
+                if key != "@language" {
+                    canonicalize_value(child, changed);
+                    continue;
+                }
+
+                if let Some(tag) = child.as_str() {
+                    if let Some(canonical) = canonicalize(tag).filter(|c| c != tag) {
+                        changed.push((tag.to_string(), canonical.clone()));
+                        *child = Value::String(canonical);
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                canonicalize_value(item, changed);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn checks_well_formed_tags() {
+        for tag in &[
+            "en", "en-us", "it-IT", "english-language", "x-default", "zh-Hant-TW", "es-419", "sl-rozaj-biske", "de-CH-1901",
+            "en-a-bbb-x-a-ccc", "zh-min-nan", "i-klingon", "qaa-Qaaa-QM-x-southern",
+        ] {
+            assert!(is_well_formed(tag), "{} should be well-formed", tag);
+        }
+
+        for tag in &["", "e", "en_US", "en-", "en-US-tags", "en-a", "en-x", "x", "de-419-DE-a", "12"] {
+            assert!(!is_well_formed(tag), "{} should not be well-formed", tag);
+        }
+    }
+
+    #[test]
+    fn canonicalizes_case() {
+        assert_eq!(canonicalize("en-us").unwrap(), "en-US");
+        assert_eq!(canonicalize("ZH-HANT-tw").unwrap(), "zh-Hant-TW");
+        assert_eq!(canonicalize("X-Default").unwrap(), "x-default");
+        assert_eq!(canonicalize("en-a-BBB-x-US").unwrap(), "en-a-bbb-x-us");
+        assert_eq!(canonicalize("EN-gb-OED").unwrap(), "en-GB-oed");
+        assert!(canonicalize("en_us").is_none());
+
+        let mut packet = json!({
+            "dc:source": { "@value": "Sample", "@language": "en-us" },
+            "dc:subject": { "@set": [{ "@value": "Beispiel", "@language": "de-DE" }], "@context": { "@language": "it-it" } }
+        });
+        assert_eq!(
+            canonicalize_languages(&mut packet),
+            vec![
+                ("en-us".to_string(), "en-US".to_string()),
+                ("it-it".to_string(), "it-IT".to_string())
+            ]
+        );
+        assert_eq!(packet["dc:source"]["@language"], json!("en-US"));
+    }
+}
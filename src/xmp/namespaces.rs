@@ -0,0 +1,355 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fmt;
+use Container::{Alt, Bag, LangAlt, Seq, Single};
+use ValueType::{AgentName, Boolean, Date, Integer, Locale, MimeType, ProperName, Rational, Real, Struct, Text, Uri, Url};
+
+/// A standard XMP namespace and its customary prefix.
+pub struct Namespace {
+    pub prefix: &'static str,
+    pub iri: &'static str,
+    pub name: &'static str,
+}
+
+/// The namespaces of the XMP specification and of the IPTC, PLUS and Creative Commons schemas
+/// commonly found in XMP packets, including the structure types used by `xmpMM` properties.
+pub const NAMESPACES: [Namespace; 14] = [
+    Namespace { prefix: "dc", iri: "http://purl.org/dc/elements/1.1/", name: "Dublin Core" },
+    Namespace { prefix: "xmp", iri: "http://ns.adobe.com/xap/1.0/", name: "XMP Basic" },
+    Namespace { prefix: "xmpRights", iri: "http://ns.adobe.com/xap/1.0/rights/", name: "XMP Rights Management" },
+    Namespace { prefix: "xmpMM", iri: "http://ns.adobe.com/xap/1.0/mm/", name: "XMP Media Management" },
+    Namespace { prefix: "stRef", iri: "http://ns.adobe.com/xap/1.0/sType/ResourceRef#", name: "Resource Reference" },
+    Namespace { prefix: "stEvt", iri: "http://ns.adobe.com/xap/1.0/sType/ResourceEvent#", name: "Resource Event" },
+    Namespace { prefix: "stDim", iri: "http://ns.adobe.com/xap/1.0/sType/Dimensions#", name: "Dimensions" },
+    Namespace { prefix: "photoshop", iri: "http://ns.adobe.com/photoshop/1.0/", name: "Photoshop" },
+    Namespace { prefix: "exif", iri: "http://ns.adobe.com/exif/1.0/", name: "EXIF" },
+    Namespace { prefix: "Iptc4xmpCore", iri: "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/", name: "IPTC Core" },
+    Namespace { prefix: "Iptc4xmpExt", iri: "http://iptc.org/std/Iptc4xmpExt/2008-02-29/", name: "IPTC Extension" },
+    Namespace { prefix: "plus", iri: "http://ns.useplus.org/ldf/xmp/1.0/", name: "PLUS" },
+    Namespace { prefix: "cc", iri: "http://creativecommons.org/ns#", name: "Creative Commons" },
+    Namespace { prefix: "rdf", iri: "http://www.w3.org/1999/02/22-rdf-syntax-ns#", name: "RDF" },
+];
+
+/// The XMP value type of a property, or of each item of an array property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValueType {
+    Text,
+    ProperName,
+    AgentName,
+    Date,
+    Integer,
+    Real,
+    Rational,
+    Boolean,
+    Uri,
+    Url,
+    MimeType,
+    Locale,
+    Struct,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Text => "Text",
+            ValueType::ProperName => "ProperName",
+            ValueType::AgentName => "AgentName",
+            ValueType::Date => "Date",
+            ValueType::Integer => "Integer",
+            ValueType::Real => "Real",
+            ValueType::Rational => "Rational",
+            ValueType::Boolean => "Boolean",
+            ValueType::Uri => "URI",
+            ValueType::Url => "URL",
+            ValueType::MimeType => "MIMEType",
+            ValueType::Locale => "Locale",
+            ValueType::Struct => "Struct",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How the values of a property are held.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Container {
+    /// A single value.
+    Single,
+    /// An unordered array, written as `@set`.
+    Bag,
+    /// An ordered array, written as `@list`.
+    Seq,
+    /// Alternatives in order of preference, written as an `rdf:Alt` object.
+    Alt,
+    /// Alternatives by language, written as an `rdf:Alt` object of `@language`/`@value` items.
+    LangAlt,
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Container::Single => "single value",
+            Container::Bag => "Bag (@set)",
+            Container::Seq => "Seq (@list)",
+            Container::Alt => "Alt (rdf:Alt)",
+            Container::LangAlt => "Lang Alt (rdf:Alt with @language and @value)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The schema of a standard XMP property.
+pub struct Property {
+    pub name: &'static str,
+    pub value_type: ValueType,
+    pub container: Container,
+    pub description: &'static str,
+}
+
+const fn property(name: &'static str, value_type: ValueType, container: Container, description: &'static str) -> Property {
+    Property { name, value_type, container, description }
+}
+
+pub const PROPERTIES: &[Property] = &[
+    property("dc:contributor", ProperName, Bag, "Contributors to the resource, other than the creators."),
+    property("dc:coverage", Text, Single, "The extent or scope of the resource."),
+    property("dc:creator", ProperName, Seq, "The creators of the resource, most significant first."),
+    property("dc:date", Date, Seq, "Dates associated with events in the life of the resource."),
+    property("dc:description", Text, LangAlt, "Textual descriptions of the content of the resource."),
+    property("dc:format", MimeType, Single, "The MIME type of the resource."),
+    property("dc:identifier", Text, Single, "An unambiguous reference to the resource."),
+    property("dc:language", Locale, Bag, "The languages used in the resource."),
+    property("dc:publisher", ProperName, Bag, "Publishers of the resource."),
+    property("dc:relation", Text, Bag, "Relationships to other documents."),
+    property("dc:rights", Text, LangAlt, "Informal rights statements."),
+    property("dc:source", Text, Single, "A related resource from which this one is derived."),
+    property("dc:subject", Text, Bag, "Keywords describing the content of the resource."),
+    property("dc:title", Text, LangAlt, "The title or name of the resource."),
+    property("dc:type", Text, Bag, "The nature or genre of the resource."),
+    property("xmp:BaseURL", Url, Single, "The base URL for relative URLs in the document."),
+    property("xmp:CreateDate", Date, Single, "The date and time the resource was created."),
+    property("xmp:CreatorTool", AgentName, Single, "The name of the tool that created the resource."),
+    property("xmp:Identifier", Text, Bag, "Unambiguous identifiers for the resource."),
+    property("xmp:Label", Text, Single, "A user-defined label, such as a workflow state."),
+    property("xmp:MetadataDate", Date, Single, "The date and time the metadata was last changed."),
+    property("xmp:ModifyDate", Date, Single, "The date and time the resource was last modified."),
+    property("xmp:Nickname", Text, Single, "A short informal name for the resource."),
+    property("xmp:Rating", Real, Single, "A user-assigned rating from -1 (rejected) through 0 (unrated) to 5."),
+    property("xmp:Thumbnails", Struct, Alt, "Thumbnail images of the resource, in different sizes or formats."),
+    property("xmpRights:Certificate", Url, Single, "A web URL for a rights management certificate."),
+    property("xmpRights:Marked", Boolean, Single, "True if the resource is rights-managed, False if it is public domain."),
+    property("xmpRights:Owner", ProperName, Bag, "The legal owners of the resource."),
+    property("xmpRights:UsageTerms", Text, LangAlt, "Instructions on how the resource can be legally used."),
+    property("xmpRights:WebStatement", Url, Single, "A web URL for a rights management statement."),
+    property("xmpMM:DerivedFrom", Struct, Single, "A reference to the resource this one was derived from."),
+    property("xmpMM:DocumentID", Uri, Single, "The common identifier for all versions and renditions of the resource."),
+    property("xmpMM:History", Struct, Seq, "The high-level actions that resulted in this resource."),
+    property("xmpMM:InstanceID", Uri, Single, "An identifier for this specific version of the resource."),
+    property("xmpMM:OriginalDocumentID", Uri, Single, "The identifier of the original resource this one is derived from."),
+    property("xmpMM:RenditionClass", Text, Single, "The rendition class of the resource, such as \"thumbnail\"."),
+    property("xmpMM:VersionID", Text, Single, "The version of the resource."),
+    property("photoshop:AuthorsPosition", Text, Single, "The job title of the creator."),
+    property("photoshop:CaptionWriter", ProperName, Single, "The person who wrote the description."),
+    property("photoshop:City", Text, Single, "The city of the location shown."),
+    property("photoshop:Country", Text, Single, "The country of the location shown."),
+    property("photoshop:Credit", Text, Single, "The credit line required when publishing the resource."),
+    property("photoshop:DateCreated", Date, Single, "The date the intellectual content was created."),
+    property("photoshop:Headline", Text, Single, "A brief synopsis of the content."),
+    property("photoshop:Instructions", Text, Single, "Special instructions for using the resource."),
+    property("photoshop:Source", Text, Single, "The original owner of the copyright."),
+    property("photoshop:State", Text, Single, "The province or state of the location shown."),
+    property("photoshop:TransmissionReference", Text, Single, "An identifier for tracking the transmission of the resource."),
+    property("photoshop:Urgency", Integer, Single, "The editorial urgency of the content, from 1 (most urgent) to 8."),
+    property("exif:DateTimeOriginal", Date, Single, "The date and time the original image was captured."),
+    property("exif:ExposureTime", Rational, Single, "The exposure time in seconds."),
+    property("exif:FNumber", Rational, Single, "The F number of the lens."),
+    property("exif:FocalLength", Rational, Single, "The focal length of the lens in millimeters."),
+    property("exif:PixelXDimension", Integer, Single, "The width of the image in pixels."),
+    property("exif:PixelYDimension", Integer, Single, "The height of the image in pixels."),
+    property("Iptc4xmpCore:CountryCode", Text, Single, "The ISO 3166 code of the country of the location shown."),
+    property("Iptc4xmpCore:CreatorContactInfo", Struct, Single, "The contact information of the creator."),
+    property("Iptc4xmpCore:IntellectualGenre", Text, Single, "The genre of the content."),
+    property("Iptc4xmpCore:Location", Text, Single, "The sublocation shown."),
+    property("Iptc4xmpCore:Scene", Text, Bag, "IPTC scene codes describing the content."),
+    property("Iptc4xmpCore:SubjectCode", Text, Bag, "IPTC subject codes describing the content."),
+    property("Iptc4xmpExt:AOTitle", Text, LangAlt, "The title of the artwork or object shown."),
+    property("Iptc4xmpExt:DigitalSourceType", Uri, Single, "The type of the source of the digital content."),
+    property("Iptc4xmpExt:LocationCreated", Struct, Bag, "The location the content was created at."),
+    property("Iptc4xmpExt:PersonInImage", Text, Bag, "The names of the people shown."),
+    property("plus:CopyrightOwner", Struct, Seq, "The owners of the copyright."),
+    property("plus:ImageCreator", Struct, Seq, "The creators of the content."),
+    property("plus:Licensor", Struct, Seq, "The licensors of the content."),
+    property("plus:Version", Text, Single, "The version of the PLUS standard used."),
+    property("cc:attributionName", Text, Single, "The name to use when attributing the work."),
+    property("cc:attributionURL", Url, Single, "The URL to use when attributing the work."),
+    property("cc:license", Uri, Single, "The license the work is published under."),
+    property("cc:morePermissions", Url, Single, "A URL with permissions beyond the license."),
+    property("cc:useGuidelines", Url, Single, "A URL with guidelines for using the work."),
+];
+
+/// Looks up a standard namespace by its prefix.
+pub fn namespace(prefix: &str) -> Option<&'static Namespace> {
+    NAMESPACES.iter().find(|n| n.prefix == prefix)
+}
+
+/// Looks up the schema of a standard property, e.g. `dc:rights`.
+pub fn property_schema(name: &str) -> Option<&'static Property> {
+    PROPERTIES.iter().find(|p| p.name == name)
+}
+
+/// Returns the prefixes of the compact IRIs used by a packet's properties and `@type` values.
+/// Absolute IRIs such as `http://...` are ignored.
+pub fn used_prefixes(packet: &Value) -> BTreeSet<String> {
+    let mut prefixes = BTreeSet::new();
+    collect_prefixes(packet, &mut prefixes);
+    prefixes
+}
+
+fn add_prefix(name: &str, prefixes: &mut BTreeSet<String>) {
+    if let Some((prefix, suffix)) = name.split_once(':') {
+        if !prefix.is_empty() && !suffix.starts_with("//") {
+            prefixes.insert(prefix.to_string());
+        }
+    }
+}
+
+fn collect_prefixes(value: &Value, prefixes: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                match key.as_str() {
+                    "@context" => continue,
+                    "@type" => {
+                        let types = value.as_array().map(|t| t.iter().collect()).unwrap_or_else(|| vec![value]);
+                        for name in types.into_iter().filter_map(Value::as_str) {
+                            add_prefix(name, prefixes);
+                        }
+                        continue;
+                    }
+                    key if key.starts_with('@') => {}
+                    property => add_prefix(property, prefixes),
+                }
+                collect_prefixes(value, prefixes);
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|i| collect_prefixes(i, prefixes)),
+        _ => {}
+    }
+}
+
+/// The outcome of completing a `@context` from the registry.
+#[derive(Default, PartialEq, Debug)]
+pub struct ContextReport {
+    /// Standard prefixes that were declared automatically.
+    pub added: Vec<String>,
+    /// Prefixes that are used but neither declared nor standard.
+    pub unknown: Vec<String>,
+    /// Standard prefixes declared with an IRI other than the standard one.
+    pub mismatched: Vec<String>,
+}
+
+/// Declares every standard prefix in `prefixes` that is missing from `context`.
+pub fn complete_context(context: &mut Map<String, Value>, prefixes: &BTreeSet<String>) -> ContextReport {
+    let mut report = ContextReport::default();
+
+    for prefix in prefixes {
+        match (context.get(prefix), namespace(prefix)) {
+            (None, Some(namespace)) => {
+                context.insert(prefix.clone(), Value::String(namespace.iri.to_string()));
+                report.added.push(prefix.clone());
+            }
+            (None, None) => report.unknown.push(prefix.clone()),
+            (Some(Value::String(iri)), Some(namespace)) if iri != namespace.iri => {
+                report.mismatched.push(prefix.clone())
+            }
+            _ => {}
+        }
+    }
+
+    report
+}
+
+/// Completes the inline `@context` of a `KHR_xmp_json_ld` packet, creating it if needed.
+/// Packets that are not objects, or whose `@context` is not an object, are left for validation
+/// to report.
+pub fn complete_packet_context(packet: &mut Value) -> ContextReport {
+    let prefixes = used_prefixes(packet);
+
+    let packet = match packet.as_object_mut() {
+        Some(p) => p,
+        None => return ContextReport::default(),
+    };
+    let context = packet
+        .entry("@context")
+        .or_insert_with(|| Value::Object(Map::new()));
+
+    match context.as_object_mut() {
+        Some(context) => complete_context(context, &prefixes),
+        None => ContextReport::default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn finds_used_prefixes() {
+        let packet = json!({
+            "@context": { "ignored": "http://example.com/" },
+            "@id": "",
+            "dc:title": { "@type": "rdf:Alt", "rdf:_1": { "@language": "en", "@value": "Title" } },
+            "xmpMM:History": { "@list": [{ "stEvt:action": "saved" }] },
+            "http://example.com/property": "value"
+        });
+
+        assert_eq!(
+            used_prefixes(&packet).into_iter().collect::<Vec<String>>(),
+            vec!["dc", "rdf", "stEvt", "xmpMM"]
+        );
+    }
+
+    #[test]
+    fn completes_packet_contexts() {
+        let mut packet = json!({
+            "@context": { "xmp": "http://example.com/not-xmp/" },
+            "dc:title": "Title",
+            "xmp:Rating": 5,
+            "foo:bar": "baz"
+        });
+
+        let report = complete_packet_context(&mut packet);
+        assert_eq!(
+            report,
+            ContextReport {
+                added: vec!["dc".to_string()],
+                unknown: vec!["foo".to_string()],
+                mismatched: vec!["xmp".to_string()],
+            }
+        );
+        assert_eq!(packet["@context"]["dc"], json!("http://purl.org/dc/elements/1.1/"));
+
+        let mut bare = json!({ "dc:title": "Title" });
+        complete_packet_context(&mut bare);
+        assert_eq!(bare["@context"], json!({ "dc": "http://purl.org/dc/elements/1.1/" }));
+
+        let mut history = json!({
+            "xmpMM:DerivedFrom": { "stRef:instanceID": "xmp.iid:1" },
+            "xmpMM:History": { "@list": [{ "stEvt:action": "saved" }] }
+        });
+        assert!(complete_packet_context(&mut history).unknown.is_empty());
+        assert_eq!(history["@context"]["stEvt"], json!("http://ns.adobe.com/xap/1.0/sType/ResourceEvent#"));
+    }
+
+    #[test]
+    fn describes_properties() {
+        let rights = property_schema("dc:rights").unwrap();
+        assert_eq!(rights.container, Container::LangAlt);
+        assert_eq!(rights.value_type, ValueType::Text);
+        assert!(property_schema("dc:unknown").is_none());
+    }
+}
@@ -0,0 +1,220 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::xmp::alternatives::{alternatives, is_alt, set_alternatives};
+use crate::xmp::language::DEFAULT_LANGUAGE;
+use crate::xmp::namespaces::{property_schema, Container};
+use serde_json::{json, Map, Value};
+
+/// A property whose value was rewritten into the JSON-LD form of its container.
+#[derive(PartialEq, Debug)]
+pub struct Normalized {
+    /// The property name, preceded by the names of the structures that contain it, e.g.
+    /// `xmpMM:History/stEvt:action`.
+    pub path: String,
+    pub container: Container,
+}
+
+/// Rewrites the values of standard properties into the JSON-LD form of their XMP container:
+///  - Bag values become `@set` arrays and Seq values become `@list` arrays,
+///  - Alt values become `rdf:Alt` objects with `rdf:_N` items,
+///  - Lang Alt items also become `@language`/`@value` objects, `x-default` if no language is given,
+///  - single values held in a one-item array are unwrapped.
+///
+/// Properties without a schema, and values that already have the right form, are left alone.
+/// Structures are normalized recursively.
+pub fn normalize_packet(packet: &mut Value) -> Vec<Normalized> {
+    let mut normalized = vec![];
+    if let Value::Object(node) = packet {
+        normalize_node(node, "", &mut normalized);
+    }
+    normalized
+}
+
+fn normalize_node(node: &mut Map<String, Value>, path: &str, normalized: &mut Vec<Normalized>) {
+    for (key, value) in node.iter_mut().filter(|(k, _)| !k.starts_with('@')) {
+        let property_path = format!("{}{}", path, key);
+
+        if let Some(schema) = property_schema(key) {
+            if let Some(rewritten) = normalize_value(value, schema.container) {
+                *value = rewritten;
+                normalized.push(Normalized {
+                    path: property_path.clone(),
+                    container: schema.container,
+                });
+            }
+        }
+
+        for structure in structures_mut(value) {
+            normalize_node(structure, &format!("{}/", property_path), normalized);
+        }
+    }
+}
+
+/// The structures held by a property value, directly or as container items.
+fn structures_mut(value: &mut Value) -> Vec<&mut Map<String, Value>> {
+    let object = match value {
+        Value::Object(o) => o,
+        Value::Array(items) => return items.iter_mut().flat_map(structures_mut).collect(),
+        _ => return vec![],
+    };
+
+    if object.contains_key("@value") {
+        vec![]
+    } else if object.contains_key("@list") || object.contains_key("@set") {
+        object.values_mut().flat_map(structures_mut).collect()
+    } else if is_alt(object) {
+        object
+            .iter_mut()
+            .filter(|(k, _)| k.starts_with("rdf:_"))
+            .flat_map(|(_, v)| structures_mut(v))
+            .collect()
+    } else {
+        vec![object]
+    }
+}
+
+/// Returns the normalized form of a value, or `None` if it already has the right form.
+fn normalize_value(value: &Value, container: Container) -> Option<Value> {
+    match container {
+        Container::Single => match value {
+            Value::Array(items) if items.len() == 1 => Some(items[0].clone()),
+            _ => None,
+        },
+        Container::Bag => to_array_container(value, "@set", "@list"),
+        Container::Seq => to_array_container(value, "@list", "@set"),
+        Container::Alt | Container::LangAlt => {
+            let is_language = container == Container::LangAlt;
+            let items = match value {
+                Value::Object(o) if is_alt(o) => {
+                    let items: Vec<Value> = alternatives(o).into_iter().cloned().collect();
+                    if !is_language || items.iter().all(is_language_value) {
+                        return None;
+                    }
+                    items
+                }
+                Value::Object(o) if o.contains_key("@list") || o.contains_key("@set") => {
+                    array_items(o).to_vec()
+                }
+                Value::Array(items) => items.clone(),
+                item => vec![item.clone()],
+            };
+            Some(to_alt(items, is_language))
+        }
+    }
+}
+
+/// Wraps a value in `keyword` (`@set` or `@list`), converting the `other` array keyword.
+fn to_array_container(value: &Value, keyword: &str, other: &str) -> Option<Value> {
+    let items = match value {
+        Value::Object(o) if o.contains_key(keyword) => return None,
+        Value::Object(o) if o.contains_key(other) => array_items(o).to_vec(),
+        Value::Object(o) if is_alt(o) => alternatives(o).into_iter().cloned().collect(),
+        Value::Array(items) => items.clone(),
+        item => vec![item.clone()],
+    };
+
+    let mut container = Map::new();
+    container.insert(keyword.to_string(), Value::Array(items));
+    Some(Value::Object(container))
+}
+
+fn array_items(object: &Map<String, Value>) -> &[Value] {
+    object
+        .get("@list")
+        .or_else(|| object.get("@set"))
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn is_language_value(value: &Value) -> bool {
+    value.get("@language").is_some() && value.get("@value").is_some()
+}
+
+fn to_alt(items: Vec<Value>, is_language: bool) -> Value {
+    let items = items
+        .into_iter()
+        .map(|item| match item {
+            Value::Object(mut o) if is_language && o.contains_key("@value") => {
+                o.entry("@language").or_insert_with(|| json!(DEFAULT_LANGUAGE));
+                Value::Object(o)
+            }
+            Value::String(s) if is_language => json!({ "@language": DEFAULT_LANGUAGE, "@value": s }),
+            item => item,
+        })
+        .collect();
+
+    let mut alt = Map::new();
+    alt.insert("@type".to_string(), json!("rdf:Alt"));
+    set_alternatives(&mut alt, items);
+    Value::Object(alt)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalizes_containers() {
+        let mut packet = json!({
+            "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+            "dc:creator": "Creator",
+            "dc:subject": ["tree", "leaf"],
+            "dc:title": "Title",
+            "dc:description": [{ "@language": "en", "@value": "Text" }, { "@language": "de", "@value": "Text" }],
+            "dc:format": ["model/gltf+json"],
+            "xmp:Thumbnails": ["a.png", "b.png"],
+            "foo:bar": ["baz"]
+        });
+
+        let paths: Vec<String> = normalize_packet(&mut packet).into_iter().map(|n| n.path).collect();
+        assert_eq!(
+            paths,
+            vec!["dc:creator", "dc:description", "dc:format", "dc:subject", "dc:title", "xmp:Thumbnails"]
+        );
+        assert_eq!(
+            packet,
+            json!({
+                "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+                "dc:creator": { "@list": ["Creator"] },
+                "dc:subject": { "@set": ["tree", "leaf"] },
+                "dc:title": { "@type": "rdf:Alt", "rdf:_1": { "@language": "x-default", "@value": "Title" } },
+                "dc:description": {
+                    "@type": "rdf:Alt",
+                    "rdf:_1": { "@language": "en", "@value": "Text" },
+                    "rdf:_2": { "@language": "de", "@value": "Text" }
+                },
+                "dc:format": "model/gltf+json",
+                "xmp:Thumbnails": { "@type": "rdf:Alt", "rdf:_1": "a.png", "rdf:_2": "b.png" },
+                "foo:bar": ["baz"]
+            })
+        );
+
+        assert!(normalize_packet(&mut packet).is_empty());
+    }
+
+    #[test]
+    fn normalizes_structures() {
+        let mut packet = json!({
+            "xmpMM:DerivedFrom": { "xmpMM:DocumentID": ["uuid:1"] },
+            "plus:Licensor": { "@list": [{ "dc:rights": "Rights" }] }
+        });
+
+        let paths: Vec<String> = normalize_packet(&mut packet).into_iter().map(|n| n.path).collect();
+        assert_eq!(paths, vec!["plus:Licensor/dc:rights", "xmpMM:DerivedFrom/xmpMM:DocumentID"]);
+        assert_eq!(packet["xmpMM:DerivedFrom"]["xmpMM:DocumentID"], json!("uuid:1"));
+    }
+
+    #[test]
+    fn keeps_normalized_sample() {
+        let mut packets = crate::io_helpers::read_json(std::fs::File::open("examples/sample.khr_xmp_json_ld.json").unwrap())
+            .unwrap()
+            .packets;
+
+        for packet in packets.iter_mut() {
+            assert!(normalize_packet(packet).is_empty());
+        }
+    }
+}
@@ -0,0 +1,294 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::json_models::khr_xmp_json_ld::KhrXmpJsonLd;
+use crate::xmp::{RDF_NAMESPACE, XML_NAMESPACE};
+use roxmltree::{Document, Node};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// Converts an XMP packet (RDF/XML) into `KHR_xmp_json_ld` packets, one per top-level
+/// `rdf:Description`. The input may be a bare `x:xmpmeta`/`rdf:RDF` document or a complete
+/// `<?xpacket?>` block, as found in `.xmp` sidecar files.
+///
+/// Containers are mapped as follows:
+///  - `rdf:Bag` becomes `@set`.
+///  - `rdf:Seq` becomes `@list`.
+///  - `rdf:Alt` becomes an object typed `rdf:Alt` with `rdf:_1`, `rdf:_2`, ... entries.
+///  - `xml:lang` becomes a `@language`/`@value` value object.
+pub fn parse_xmp(text: &str) -> Result<KhrXmpJsonLd, String> {
+    let document = Document::parse(text.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("Unable to parse XMP input: {}", e))?;
+
+    let packets = document
+        .descendants()
+        .filter(|n| n.has_tag_name((RDF_NAMESPACE, "Description")))
+        .filter(|n| {
+            n.parent_element()
+                .is_some_and(|p| p.has_tag_name((RDF_NAMESPACE, "RDF")))
+        })
+        .map(convert_description)
+        .collect::<Result<Vec<Value>, String>>()?;
+
+    if packets.is_empty() {
+        return Err("No rdf:Description elements found in the XMP input.".to_string());
+    }
+
+    Ok(KhrXmpJsonLd { packets })
+}
+
+/// The prefixes used while converting a single packet. Only these end up in its `@context`.
+#[derive(Default)]
+struct Context {
+    prefixes: BTreeMap<String, String>,
+}
+
+impl Context {
+    /// Returns the compact `prefix:name` form of an XML name, registering the prefix if needed.
+    fn compact(&mut self, node: Node, namespace: Option<&str>, name: &str) -> Result<String, String> {
+        let namespace = match namespace {
+            Some(ns) => ns,
+            None => return Err(format!("XMP property \"{}\" is not in a namespace.", name)),
+        };
+
+        let preferred = node
+            .lookup_prefix(namespace)
+            .filter(|p| !p.is_empty())
+            .unwrap_or("ns");
+
+        let mut prefix = preferred.to_string();
+        let mut counter = 1;
+        while let Some(existing) = self.prefixes.get(&prefix) {
+            if existing == namespace {
+                break;
+            }
+            counter += 1;
+            prefix = format!("{}{}", preferred, counter);
+        }
+
+        self.prefixes.insert(prefix.clone(), namespace.to_string());
+        Ok(format!("{}:{}", prefix, name))
+    }
+
+    fn into_value(self) -> Value {
+        Value::Object(
+            self.prefixes
+                .into_iter()
+                .map(|(prefix, namespace)| (prefix, Value::String(namespace)))
+                .collect(),
+        )
+    }
+}
+
+fn convert_description(description: Node) -> Result<Value, String> {
+    let mut context = Context::default();
+    let mut packet = convert_properties(description, &mut context)?;
+
+    packet.insert(
+        "@id".to_string(),
+        Value::String(description.attribute((RDF_NAMESPACE, "about")).unwrap_or_default().to_string()),
+    );
+    packet.insert("@context".to_string(), context.into_value());
+
+    Ok(Value::Object(packet))
+}
+
+/// Converts the properties of a node, written either as attributes or as child elements.
+fn convert_properties(node: Node, context: &mut Context) -> Result<Map<String, Value>, String> {
+    let mut properties = Map::new();
+
+    for attribute in node.attributes() {
+        match attribute.namespace() {
+            None | Some(RDF_NAMESPACE) | Some(XML_NAMESPACE) => continue,
+            namespace => {
+                let key = context.compact(node, namespace, attribute.name())?;
+                let value = with_language(attribute.value(), language_of(node));
+                properties.insert(key, value);
+            }
+        }
+    }
+
+    for child in node.children().filter(|c| c.is_element()) {
+        let name = child.tag_name();
+        let key = context.compact(child, name.namespace(), name.name())?;
+        properties.insert(key, convert_value(child, context)?);
+    }
+
+    Ok(properties)
+}
+
+/// Converts the value of a property element or an `rdf:li` item.
+fn convert_value(element: Node, context: &mut Context) -> Result<Value, String> {
+    if let Some(resource) = element.attribute((RDF_NAMESPACE, "resource")) {
+        return Ok(json!({ "@id": resource }));
+    }
+
+    if element.attribute((RDF_NAMESPACE, "parseType")) == Some("Resource") {
+        return Ok(Value::Object(convert_properties(element, context)?));
+    }
+
+    let child = match element.children().find(|c| c.is_element()) {
+        Some(c) => c,
+        None => {
+            // Qualifiers written as attributes make this a structure rather than a simple value.
+            let properties = convert_properties(element, context)?;
+            if !properties.is_empty() {
+                return Ok(Value::Object(properties));
+            }
+
+            return Ok(with_language(element.text().unwrap_or_default(), language_of(element)));
+        }
+    };
+
+    if child.has_tag_name((RDF_NAMESPACE, "Bag")) {
+        Ok(json!({ "@set": convert_items(child, context)? }))
+    } else if child.has_tag_name((RDF_NAMESPACE, "Seq")) {
+        Ok(json!({ "@list": convert_items(child, context)? }))
+    } else if child.has_tag_name((RDF_NAMESPACE, "Alt")) {
+        convert_alternative(child, context)
+    } else if child.has_tag_name((RDF_NAMESPACE, "Description")) {
+        Ok(Value::Object(convert_properties(child, context)?))
+    } else {
+        Err(format!(
+            "Unsupported XMP value for property \"{}\".",
+            element.tag_name().name()
+        ))
+    }
+}
+
+fn convert_items(container: Node, context: &mut Context) -> Result<Vec<Value>, String> {
+    container
+        .children()
+        .filter(|c| c.has_tag_name((RDF_NAMESPACE, "li")))
+        .map(|item| convert_value(item, context))
+        .collect()
+}
+
+fn convert_alternative(container: Node, context: &mut Context) -> Result<Value, String> {
+    let alt_type = context.compact(container, Some(RDF_NAMESPACE), "Alt")?;
+
+    let mut alternative = Map::new();
+    alternative.insert("@type".to_string(), Value::String(alt_type));
+
+    for (index, item) in convert_items(container, context)?.into_iter().enumerate() {
+        let key = context.compact(container, Some(RDF_NAMESPACE), &format!("_{}", index + 1))?;
+        let item = match item {
+            Value::String(s) => json!({ "@value": s }),
+            other => other,
+        };
+        alternative.insert(key, item);
+    }
+
+    Ok(Value::Object(alternative))
+}
+
+/// Finds the `xml:lang` in scope for a node. Language tags are inherited from ancestors.
+fn language_of<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.ancestors()
+        .take_while(|n| !n.has_tag_name((RDF_NAMESPACE, "RDF")))
+        .find_map(|n| n.attribute((XML_NAMESPACE, "lang")))
+}
+
+fn with_language(value: &str, language: Option<&str>) -> Value {
+    match language {
+        Some(language) => json!({ "@value": value, "@language": language }),
+        None => Value::String(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAMPLE_XMP: &str = r#"<?xpacket begin="﻿" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about=""
+        xmlns:dc="http://purl.org/dc/elements/1.1/"
+        xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+        xmp:CreatorTool="Blender">
+      <dc:creator>
+        <rdf:Seq>
+          <rdf:li>CreatorName</rdf:li>
+        </rdf:Seq>
+      </dc:creator>
+      <dc:subject>
+        <rdf:Bag>
+          <rdf:li>architecture</rdf:li>
+          <rdf:li xml:lang="de-DE">Architektur</rdf:li>
+        </rdf:Bag>
+      </dc:subject>
+      <dc:title>
+        <rdf:Alt>
+          <rdf:li xml:lang="x-default">MyModel</rdf:li>
+          <rdf:li xml:lang="it-IT">Mio Modello</rdf:li>
+        </rdf:Alt>
+      </dc:title>
+      <dc:source xml:lang="en-US">Sample files</dc:source>
+      <dc:relation rdf:resource="https://www.khronos.org/"/>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn converts_containers_and_languages() {
+        let packets = parse_xmp(SAMPLE_XMP).unwrap().packets;
+        assert_eq!(packets.len(), 1);
+        assert_eq!(
+            packets[0],
+            json!({
+                "@context": {
+                    "dc": "http://purl.org/dc/elements/1.1/",
+                    "rdf": "http://www.w3.org/1999/02/22-rdf-syntax-ns#",
+                    "xmp": "http://ns.adobe.com/xap/1.0/"
+                },
+                "@id": "",
+                "xmp:CreatorTool": "Blender",
+                "dc:creator": { "@list": ["CreatorName"] },
+                "dc:subject": {
+                    "@set": [
+                        "architecture",
+                        { "@value": "Architektur", "@language": "de-DE" }
+                    ]
+                },
+                "dc:title": {
+                    "@type": "rdf:Alt",
+                    "rdf:_1": { "@language": "x-default", "@value": "MyModel" },
+                    "rdf:_2": { "@language": "it-IT", "@value": "Mio Modello" }
+                },
+                "dc:source": { "@value": "Sample files", "@language": "en-US" },
+                "dc:relation": { "@id": "https://www.khronos.org/" }
+            })
+        );
+    }
+
+    #[test]
+    fn converts_every_description_and_structure() {
+        let xmp = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:format>model/gltf-binary</dc:format>
+  </rdf:Description>
+  <rdf:Description rdf:about="" xmlns:Iptc4xmpCore="http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/">
+    <Iptc4xmpCore:CreatorContactInfo rdf:parseType="Resource">
+      <Iptc4xmpCore:CiEmailWork>someone@example.com</Iptc4xmpCore:CiEmailWork>
+    </Iptc4xmpCore:CreatorContactInfo>
+  </rdf:Description>
+</rdf:RDF>"#;
+
+        let packets = parse_xmp(xmp).unwrap().packets;
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0]["dc:format"], json!("model/gltf-binary"));
+        assert_eq!(
+            packets[1]["Iptc4xmpCore:CreatorContactInfo"],
+            json!({ "Iptc4xmpCore:CiEmailWork": "someone@example.com" })
+        );
+    }
+
+    #[test]
+    fn fails_without_descriptions() {
+        assert!(parse_xmp("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>").is_err());
+        assert!(parse_xmp("not xml").is_err());
+    }
+}
@@ -0,0 +1,352 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::xmp::{escape_xml, RDF_NAMESPACE};
+use serde_json::{Map, Value};
+
+const XPACKET_ID: &str = "W5M0MpCehiHzreSzNTczkc9d";
+const PADDING_LINE_LENGTH: usize = 100;
+
+/// Serializes `KHR_xmp_json_ld` packets into a single XMP packet (RDF/XML), wrapped in
+/// `<?xpacket?>` processing instructions. Each packet becomes one `rdf:Description`.
+///
+/// Values are mapped back as follows:
+///  - `@set` (or a plain JSON array) becomes `rdf:Bag`.
+///  - `@list` becomes `rdf:Seq`.
+///  - Objects typed `rdf:Alt` become `rdf:Alt`, ordered by their `rdf:_N` keys.
+///  - `@language`/`@value` value objects become text with `xml:lang`.
+///  - A default `@language` in a packet or embedded `@context` becomes `xml:lang` on the
+///    element it applies to.
+///  - `@id` objects become `rdf:resource` references.
+///  - Any other object becomes a structure using `rdf:parseType="Resource"`.
+///
+/// `padding` bytes of whitespace are added before the closing processing instruction so that
+/// other tools can edit the packet in place.
+pub fn write_xmp(packets: &[Value], padding: usize) -> Result<String, String> {
+    let mut out = String::new();
+    out.push_str(&format!("<?xpacket begin=\"\u{feff}\" id=\"{}\"?>\n", XPACKET_ID));
+    out.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+    out.push_str(&format!(" <rdf:RDF xmlns:rdf=\"{}\">\n", RDF_NAMESPACE));
+
+    for (index, packet) in packets.iter().enumerate() {
+        write_description(&mut out, packet, index)?;
+    }
+
+    out.push_str(" </rdf:RDF>\n");
+    out.push_str("</x:xmpmeta>\n");
+
+    let mut remaining = padding;
+    while remaining > 0 {
+        let length = remaining.min(PADDING_LINE_LENGTH);
+        out.push_str(&" ".repeat(length - 1));
+        out.push('\n');
+        remaining -= length;
+    }
+
+    out.push_str("<?xpacket end=\"w\"?>\n");
+
+    Ok(out)
+}
+
+/// The prefixes declared by the packet currently being written.
+struct Packet<'a> {
+    index: usize,
+    context: &'a Map<String, Value>,
+}
+
+impl<'a> Packet<'a> {
+    /// Ensures a `prefix:name` key can be written as an XML name.
+    fn check_name(&self, key: &str) -> Result<(), String> {
+        let prefix = match key.find(':') {
+            Some(i) if i > 0 && i < key.len() - 1 => &key[..i],
+            _ => {
+                return Err(format!(
+                    "Property \"{}\" in packet {} is not a compact IRI (prefix:name).",
+                    key, self.index
+                ))
+            }
+        };
+
+        if prefix == "rdf" || self.context.get(prefix).is_some_and(|v| v.is_string()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Prefix \"{}\" is not declared in the @context of packet {}.",
+                prefix, self.index
+            ))
+        }
+    }
+}
+
+fn write_description(out: &mut String, packet: &Value, index: usize) -> Result<(), String> {
+    let packet = match packet {
+        Value::Object(p) => p,
+        _ => return Err(format!("Packet {} is not a JSON object.", index)),
+    };
+
+    let empty = Map::new();
+    let context = match packet.get("@context") {
+        Some(Value::Object(c)) => c,
+        Some(_) => return Err(format!("The @context of packet {} must be a JSON object.", index)),
+        None => &empty,
+    };
+    let writer = Packet { index, context };
+
+    let about = packet.get("@id").and_then(Value::as_str).unwrap_or_default();
+    out.push_str(&format!("  <rdf:Description rdf:about=\"{}\"", escape_xml(about)));
+    for (prefix, namespace) in context {
+        // Keywords such as `@language` or `@vocab` are not prefixes.
+        if prefix.starts_with('@') {
+            continue;
+        }
+        if let Some(namespace) = namespace.as_str() {
+            if prefix == "rdf" && namespace == RDF_NAMESPACE {
+                continue;
+            }
+            out.push_str(&format!("\n      xmlns:{}=\"{}\"", prefix, escape_xml(namespace)));
+        }
+    }
+    if let Some(Value::String(language)) = context.get("@language") {
+        out.push_str(&format!("\n      xml:lang=\"{}\"", escape_xml(language)));
+    }
+    out.push_str(">\n");
+
+    write_properties(out, &writer, packet, 3)?;
+
+    out.push_str("  </rdf:Description>\n");
+
+    Ok(())
+}
+
+fn write_properties(
+    out: &mut String,
+    packet: &Packet,
+    properties: &Map<String, Value>,
+    depth: usize,
+) -> Result<(), String> {
+    for (key, value) in properties {
+        if key.starts_with('@') || value.is_null() {
+            continue;
+        }
+
+        packet.check_name(key)?;
+        write_element(out, packet, key, value, depth)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a property element or `rdf:li` item holding the given value.
+fn write_element(
+    out: &mut String,
+    packet: &Packet,
+    name: &str,
+    value: &Value,
+    depth: usize,
+) -> Result<(), String> {
+    let indent = " ".repeat(depth);
+
+    let object = match value {
+        Value::Object(o) => o,
+        Value::Array(items) => {
+            return write_container(out, packet, name, "", "rdf:Bag", items.iter().collect(), depth)
+        }
+        simple => {
+            out.push_str(&format!("{}<{}>{}</{}>\n", indent, name, escape_xml(&simple_text(simple)), name));
+            return Ok(());
+        }
+    };
+
+    // A default language set by an embedded `@context` applies to everything inside the element.
+    let scope = match object.get("@context").and_then(|c| c.get("@language")) {
+        Some(Value::String(language)) => format!(" xml:lang=\"{}\"", escape_xml(language)),
+        Some(Value::Null) => " xml:lang=\"\"".to_string(),
+        _ => String::new(),
+    };
+
+    if let Some(Value::Array(items)) = object.get("@set") {
+        return write_container(out, packet, name, &scope, "rdf:Bag", items.iter().collect(), depth);
+    }
+
+    if let Some(Value::Array(items)) = object.get("@list") {
+        return write_container(out, packet, name, &scope, "rdf:Seq", items.iter().collect(), depth);
+    }
+
+    if object.get("@type").and_then(Value::as_str) == Some("rdf:Alt") {
+        let mut items: Vec<(u64, &Value)> = object
+            .iter()
+            .filter_map(|(k, v)| k.strip_prefix("rdf:_").and_then(|n| n.parse().ok()).map(|n| (n, v)))
+            .collect();
+        items.sort_by_key(|(n, _)| *n);
+        let items = items.into_iter().map(|(_, v)| v).collect();
+
+        return write_container(out, packet, name, &scope, "rdf:Alt", items, depth);
+    }
+
+    if let Some(value) = object.get("@value") {
+        let language = match object.get("@language") {
+            Some(Value::String(l)) => format!(" xml:lang=\"{}\"", escape_xml(l)),
+            _ => scope,
+        };
+        out.push_str(&format!(
+            "{}<{}{}>{}</{}>\n",
+            indent,
+            name,
+            language,
+            escape_xml(&simple_text(value)),
+            name
+        ));
+        return Ok(());
+    }
+
+    if let Some(Value::String(id)) = object.get("@id") {
+        if object.len() == 1 {
+            out.push_str(&format!("{}<{} rdf:resource=\"{}\"/>\n", indent, name, escape_xml(id)));
+            return Ok(());
+        }
+    }
+
+    if is_language_map(object) {
+        let items = object
+            .iter()
+            .map(|(language, text)| {
+                let mut item = Map::new();
+                item.insert("@language".to_string(), Value::String(language.clone()));
+                item.insert("@value".to_string(), text.clone());
+                Value::Object(item)
+            })
+            .collect::<Vec<Value>>();
+
+        return write_container(out, packet, name, &scope, "rdf:Alt", items.iter().collect(), depth);
+    }
+
+    out.push_str(&format!("{}<{}{} rdf:parseType=\"Resource\">\n", indent, name, scope));
+    write_properties(out, packet, object, depth + 1)?;
+    out.push_str(&format!("{}</{}>\n", indent, name));
+
+    Ok(())
+}
+
+/// Writes a container element. `scope` holds the `xml:lang` attribute of an embedded default
+/// language, if any.
+fn write_container(
+    out: &mut String,
+    packet: &Packet,
+    name: &str,
+    scope: &str,
+    container: &str,
+    items: Vec<&Value>,
+    depth: usize,
+) -> Result<(), String> {
+    let indent = " ".repeat(depth);
+
+    out.push_str(&format!("{}<{}{}>\n", indent, name, scope));
+    out.push_str(&format!("{} <{}>\n", indent, container));
+    for item in items {
+        write_element(out, packet, "rdf:li", item, depth + 2)?;
+    }
+    out.push_str(&format!("{} </{}>\n", indent, container));
+    out.push_str(&format!("{}</{}>\n", indent, name));
+
+    Ok(())
+}
+
+/// Legacy `KHR_xmp` packets may store language alternatives as `{ "en-us": "text" }`.
+fn is_language_map(object: &Map<String, Value>) -> bool {
+    !object.is_empty()
+        && object
+            .iter()
+            .all(|(k, v)| v.is_string() && !k.contains(':') && !k.starts_with('@'))
+}
+
+fn simple_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        // XMP spells booleans with a leading capital.
+        Value::Bool(true) => "True".to_string(),
+        Value::Bool(false) => "False".to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::xmp::reader::parse_xmp;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_through_reader() {
+        let packets = vec![
+            json!({
+                "@context": {
+                    "dc": "http://purl.org/dc/elements/1.1/",
+                    "rdf": "http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+                },
+                "@id": "",
+                "dc:creator": { "@list": ["A & B", "C"] },
+                "dc:subject": { "@set": ["one", { "@value": "zwei", "@language": "de-DE" }] },
+                "dc:title": {
+                    "@type": "rdf:Alt",
+                    "rdf:_1": { "@language": "x-default", "@value": "Title" },
+                    "rdf:_2": { "@language": "it-IT", "@value": "Titolo" }
+                },
+                "dc:relation": { "@id": "https://www.khronos.org/" }
+            }),
+            json!({
+                "@context": { "xmp": "http://ns.adobe.com/xap/1.0/" },
+                "@id": "",
+                "xmp:Rating": "5"
+            }),
+        ];
+
+        let xmp = write_xmp(&packets, 0).unwrap();
+        assert_eq!(parse_xmp(&xmp).unwrap().packets, packets);
+    }
+
+    #[test]
+    fn writes_default_languages_as_xml_lang() {
+        let packet = json!({
+            "@context": { "@language": "en-US", "@vocab": "http://example.com/", "dc": "http://purl.org/dc/elements/1.1/" },
+            "@id": "",
+            "dc:source": "Sample files",
+            "dc:subject": {
+                "@context": { "@language": "de-DE" },
+                "@set": ["Beispiel", { "@value": "Sample", "@language": "en-US" }]
+            }
+        });
+
+        let xmp = write_xmp(&[packet], 0).unwrap();
+        assert!(!xmp.contains("xmlns:@"));
+        assert_eq!(
+            parse_xmp(&xmp).unwrap().packets,
+            vec![json!({
+                "@context": { "dc": "http://purl.org/dc/elements/1.1/" },
+                "@id": "",
+                "dc:source": { "@value": "Sample files", "@language": "en-US" },
+                "dc:subject": {
+                    "@set": [
+                        { "@value": "Beispiel", "@language": "de-DE" },
+                        { "@value": "Sample", "@language": "en-US" }
+                    ]
+                }
+            })]
+        );
+    }
+
+    #[test]
+    fn adds_padding() {
+        let xmp = write_xmp(&[json!({ "@id": "" })], 2048).unwrap();
+        let padding = &xmp[xmp.find("</x:xmpmeta>\n").unwrap() + 13..xmp.find("<?xpacket end").unwrap()];
+
+        assert_eq!(padding.len(), 2048);
+        assert!(padding.trim().is_empty());
+    }
+
+    #[test]
+    fn fails_on_undeclared_prefix() {
+        let packet = json!({ "@context": {}, "dc:title": "Title" });
+        assert!(write_xmp(&[packet], 0).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+// SPDX-FileCopyrightText: 2020 2014-2020 The Khronos Group Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const BOX_PATH: &str = "./examples/Box.gltf";
+const BOX_GLB_PATH: &str = "./examples/Box.glb";
+const JSON_LD_PATH: &str = "./examples/sample.khr_xmp_json_ld.json";
+
+/// Runs the CLI with the given arguments, feeding `stdin` to it.
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gltfxmp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Returns an empty directory for a single test.
+fn scratch_directory(test: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("gltfxmp-cli-{}-{}", std::process::id(), test));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn copy_example(example: &str, directory: &Path, name: &str) -> String {
+    let path = directory.join(name);
+    fs::copy(example, &path).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn asset_packet(gltf: &Value) -> &Value {
+    &gltf["asset"]["extensions"]["KHR_xmp_json_ld"]["packet"]
+}
+
+#[test]
+fn reads_stdin_and_writes_stdout() {
+    let input = fs::read(BOX_PATH).unwrap();
+    let output = run(&["-i", "-", "-o", "-", "-j", JSON_LD_PATH], &input);
+
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    let gltf: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(asset_packet(&gltf), 0);
+    assert!(gltf["extensionsUsed"].as_array().unwrap().contains(&"KHR_xmp_json_ld".into()));
+}
+
+#[test]
+fn detects_binary_files_without_an_extension() {
+    let directory = scratch_directory("sniff");
+    let input = copy_example(BOX_GLB_PATH, &directory, "model");
+    let output_path = directory.join("updated");
+    let output = run(&["-i", &input, "-o", output_path.to_str().unwrap(), "-j", JSON_LD_PATH], b"");
+
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(&fs::read(&output_path).unwrap()[..4], b"glTF");
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn replaces_files_in_place_with_a_backup() {
+    let directory = scratch_directory("in-place");
+    let input = copy_example(BOX_PATH, &directory, "Box.gltf");
+    let original = fs::read(&input).unwrap();
+
+    let output = run(&["-i", &input, "--in-place", "--backup=.orig", "-j", JSON_LD_PATH, "--apply", "packet=0:nodes[0]"], b"");
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+
+    assert_eq!(fs::read(format!("{}.orig", input)).unwrap(), original);
+    let gltf: Value = serde_json::from_slice(&fs::read(&input).unwrap()).unwrap();
+    assert_eq!(gltf["nodes"][0]["extensions"]["KHR_xmp_json_ld"]["packet"], 0);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn dry_run_reports_changes_without_writing() {
+    let directory = scratch_directory("dry-run");
+    let input = copy_example(BOX_PATH, &directory, "Box.gltf");
+    let original = fs::read(&input).unwrap();
+
+    let output = run(&["-i", &input, "--dry-run", "-j", JSON_LD_PATH, "--apply", "packet=0:nodes[0]"], b"");
+    assert_eq!(output.status.code(), Some(2), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("nodes[0]: packet 0 added"));
+
+    assert_eq!(fs::read(&input).unwrap(), original);
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn diffs_files() {
+    let directory = scratch_directory("diff");
+    let updated = directory.join("updated.gltf");
+    let output = run(&["-i", BOX_PATH, "-o", updated.to_str().unwrap(), "-j", JSON_LD_PATH, "--apply", "packet=0:nodes[0]"], b"");
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output = run(&["--diff", BOX_PATH, updated.to_str().unwrap()], b"");
+    assert_eq!(output.status.code(), Some(2), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("nodes[0]: packet 0 added"));
+
+    assert_eq!(run(&["--diff", BOX_PATH, BOX_PATH], b"").status.code(), Some(0));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn appending_keeps_existing_references() {
+    let input = fs::read(BOX_PATH).unwrap();
+    let before: Value = serde_json::from_slice(&input).unwrap();
+    let output = run(&["-i", "-", "-o", "-", "-j", JSON_LD_PATH, "--mode", "append"], &input);
+
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    let after: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(asset_packet(&after), asset_packet(&before));
+}
+
+#[test]
+fn rejects_indices_past_the_largest_index() {
+    let input = fs::read(BOX_PATH).unwrap();
+    let output = run(&["-i", "-", "-o", "-", "-j", JSON_LD_PATH, "--apply", "packet=0:nodes[18446744073709551615]"], &input);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("out of range"));
+}